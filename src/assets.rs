@@ -0,0 +1,51 @@
+use std::fs;
+use std::path::Path;
+
+/// Pair each resource name with its contents, compiled in
+#[cfg(feature = "embed")]
+macro_rules! embed {
+	($($name:literal),* $(,)?) => {
+		[$(($name, include_bytes!(concat!("../res/", $name)) as &[u8])),*]
+	};
+}
+
+/// Default resources compiled in, by path relative to the resources directory
+#[cfg(feature = "embed")]
+const EMBEDDED: [(&str, &[u8]); 15] = embed![
+	"images/empty.png",
+	"images/store.png",
+	"images/stored.png",
+	"images/crate.png",
+	"images/wall.png",
+	"images/playerw.png",
+	"images/splayerw.png",
+	"images/playern.png",
+	"images/splayern.png",
+	"images/playere.png",
+	"images/splayere.png",
+	"images/players.png",
+	"images/splayers.png",
+	"fonts/RobotoMono-Regular.ttf",
+	"levels/levels.txt",
+];
+
+/// Embedded copy of the resource `name`, eg: "images/wall.png"; always None without
+/// the `embed` feature
+#[cfg_attr(not(feature = "embed"), allow(unused_variables))]
+pub fn embedded(name: &str) -> Option<&'static [u8]> {
+	#[cfg(feature = "embed")]
+	{
+		if let Some(&(_, bytes)) = EMBEDDED.iter().find(|(n, _)| *n == name) {
+			return Some(bytes);
+		}
+	}
+	None
+}
+
+/// Contents of the resource `name`, from `res_dir` if the file exists there, else
+/// from its embedded copy
+pub fn read(res_dir: &Path, name: &str) -> Option<Vec<u8>> {
+	fs::read(res_dir.join(name))
+		.ok()
+		.or_else(|| embedded(name).map(<[u8]>::to_vec))
+}
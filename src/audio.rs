@@ -0,0 +1,127 @@
+use std::path::Path;
+
+#[cfg(feature = "audio")]
+use sdl2::mixer::{Channel, Chunk, Music, Sdl2MixerContext, MAX_VOLUME};
+
+#[derive(Clone, Copy)]
+pub enum Sound {
+	Step,
+	Push,
+	Store,
+	Undo,
+	Win,
+}
+
+#[cfg(feature = "audio")]
+const SOUND_FILES: [&str; 5] = ["step.wav", "push.wav", "store.wav", "undo.wav", "win.wav"];
+#[cfg(feature = "audio")]
+const MUSIC_FILE: &str = "music.ogg";
+const VOLUME_STEP: u8 = 10;
+
+/// Sound effects and background music, silent without the `audio` feature or an audio device
+pub struct Audio {
+	volume: u8, // percent
+	muted: bool,
+	#[cfg(feature = "audio")]
+	mixer: Option<Mixer>,
+}
+
+#[cfg(feature = "audio")]
+struct Mixer {
+	_context: Sdl2MixerContext,
+	chunks: Vec<Option<Chunk>>,
+	_music: Option<Music<'static>>,
+}
+
+#[cfg(feature = "audio")]
+impl Mixer {
+	fn new(sounds_dir: &Path) -> Result<Mixer, String> {
+		sdl2::mixer::open_audio(44_100, sdl2::mixer::DEFAULT_FORMAT, 2, 1024)?;
+		let context = sdl2::mixer::init(sdl2::mixer::InitFlag::OGG)?;
+		let chunks = SOUND_FILES
+			.iter()
+			.map(|file| Chunk::from_file(sounds_dir.join(file)).ok())
+			.collect();
+		let music = Music::from_file(sounds_dir.join(MUSIC_FILE)).ok();
+		if let Some(music) = &music {
+			music.play(-1)?;
+		}
+		Ok(Mixer {
+			_context: context,
+			chunks,
+			_music: music,
+		})
+	}
+}
+
+impl Audio {
+	/// Open the audio device and load the sounds found in `res/sounds`; on failure, stay silent
+	#[cfg_attr(not(feature = "audio"), allow(unused_variables))]
+	pub fn new(res_dir: &Path, volume: u8, muted: bool) -> Audio {
+		let mut audio = Audio {
+			volume: volume.min(100),
+			muted,
+			#[cfg(feature = "audio")]
+			mixer: Mixer::new(&res_dir.join("sounds"))
+				.map_err(|e| println!("Audio disabled: {}", e))
+				.ok(),
+		};
+		audio.apply_volume();
+		audio
+	}
+
+	#[cfg_attr(not(feature = "audio"), allow(unused_variables))]
+	pub fn play(&self, sound: Sound) {
+		#[cfg(feature = "audio")]
+		{
+			if let Some(Some(chunk)) = self.mixer.as_ref().map(|m| &m.chunks[sound as usize]) {
+				// running out of channels only drops the sound
+				let _ = Channel::all().play(chunk, 0);
+			}
+		}
+	}
+
+	fn apply_volume(&mut self) {
+		#[cfg(feature = "audio")]
+		{
+			if self.mixer.is_some() {
+				let volume = if self.muted {
+					0
+				} else {
+					self.volume as i32 * MAX_VOLUME / 100
+				};
+				Channel::all().set_volume(volume);
+				Music::set_volume(volume);
+			}
+		}
+	}
+
+	pub fn volume_up(&mut self) {
+		self.volume = (self.volume + VOLUME_STEP).min(100);
+		self.muted = false;
+		self.apply_volume();
+	}
+
+	pub fn volume_down(&mut self) {
+		self.volume = self.volume.saturating_sub(VOLUME_STEP);
+		self.apply_volume();
+	}
+
+	pub fn toggle_mute(&mut self) {
+		self.muted = !self.muted;
+		self.apply_volume();
+	}
+
+	pub fn muted(&self) -> bool {
+		self.muted
+	}
+
+	/// Volume percent, 0 when muted
+	pub fn volume(&self) -> u8 {
+		if self.muted {
+			0
+		} else {
+			self.volume
+		}
+	}
+}
@@ -0,0 +1,424 @@
+use cratesan::dirs::{data_dir, res_dir};
+use cratesan::menu::{self, Entry, PauseMenu};
+use cratesan::stats::{self, Outcome};
+use cratesan::tile::{self, Tile};
+use cratesan::topology::Topology;
+use cratesan::{
+	bundled_levels, Engine, Status, CRATE, C_CRATE, C_EMPTY, C_PLAYER, C_SPLAYER, C_STORE,
+	C_STORED, C_WALL, SCORES_FILE, WALL,
+};
+
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{
+	poll, read, DisableFocusChange, EnableFocusChange, Event, KeyCode, KeyEvent, KeyEventKind,
+};
+use crossterm::style::{Color, Print, ResetColor, SetForegroundColor};
+use crossterm::terminal::{
+	disable_raw_mode, enable_raw_mode, size, Clear, ClearType, EnterAlternateScreen,
+	LeaveAlternateScreen,
+};
+use crossterm::{execute, queue};
+
+use std::io::{stdout, Result, Stdout, Write};
+use std::time::Duration;
+
+const HELP: &str =
+	"arrows/7913 move  tab worker  u/y undo/redo  s/l snap  r restart  n/p level  v pull  i stats  q quit";
+const TICK_MS: u64 = 250;
+
+/// XSB character, Unicode character and colour of each cell
+const GLYPHS: [(char, char, Color); 6] = [
+	(C_EMPTY, ' ', Color::Reset),
+	(C_STORE, '·', Color::Yellow),
+	(C_CRATE, '▪', Color::DarkYellow),
+	(C_STORED, '▪', Color::Green),
+	(C_WALL, '█', Color::DarkGrey),
+	(C_PLAYER, '☻', Color::Cyan),
+];
+/// Colors of the colored crates once stored, and before, in color::COLORS order
+const TINTS: [(Color, Color); 6] = [
+	(Color::Red, Color::DarkRed),
+	(Color::Green, Color::DarkGreen),
+	(Color::Blue, Color::DarkBlue),
+	(Color::Yellow, Color::DarkYellow),
+	(Color::Cyan, Color::DarkCyan),
+	(Color::Magenta, Color::DarkMagenta),
+];
+const G_EMPTY: usize = 0;
+const G_STORE: usize = 1;
+const G_CRATE: usize = 2;
+const G_STORED: usize = 3;
+const G_WALL: usize = 4;
+const G_PLAYER: usize = 5;
+
+const PAUSE_HELP: &str = "up/down select  left/right change  Enter confirm  space resume";
+/// Rows of the settings page of the pause menu: characters, back
+const SETTINGS_ROWS: usize = 2;
+
+/// Frontend state besides the engine
+struct Ui {
+	unicode: bool,
+	stats: bool, // statistics shown instead of the level
+	menu: PauseMenu,
+}
+
+/// Restores the terminal when dropped, even on panic
+struct Terminal {
+	out: Stdout,
+}
+
+impl Terminal {
+	fn new() -> Result<Terminal> {
+		let mut out = stdout();
+		enable_raw_mode()?;
+		execute!(out, EnterAlternateScreen, EnableFocusChange, Hide)?;
+		Ok(Terminal { out })
+	}
+}
+
+impl Drop for Terminal {
+	fn drop(&mut self) {
+		let _ = execute!(
+			self.out,
+			ResetColor,
+			Show,
+			DisableFocusChange,
+			LeaveAlternateScreen
+		);
+		let _ = disable_raw_mode();
+	}
+}
+
+/// First cell to show so that `target` is centered in `view`, clamped to the map `size`
+fn scroll(target: usize, view: usize, size: usize) -> usize {
+	if size <= view {
+		0
+	} else {
+		target.saturating_sub(view / 2).min(size - view)
+	}
+}
+
+/// Character and colour of the special tile `tile`
+fn tile_glyph(tile: Tile, unicode: bool) -> (char, Color) {
+	match tile {
+		Tile::OneWay(dx, dy) if unicode => {
+			let c = match (dx, dy) {
+				(-1, 0) => '←',
+				(0, -1) => '↑',
+				(1, 0) => '→',
+				_ => '↓',
+			};
+			(c, Color::Grey)
+		}
+		Tile::Ice if unicode => ('░', Color::Blue),
+		Tile::Ice => ('~', Color::Blue),
+		Tile::Teleporter(_) => (tile::to_char(tile), Color::Magenta),
+		_ => (tile::to_char(tile), Color::Grey),
+	}
+}
+
+fn draw_level(
+	out: &mut Stdout,
+	engine: &Engine,
+	unicode: bool,
+	cols: usize,
+	rows: usize,
+) -> Result<()> {
+	let level = &engine.levels[engine.level];
+	let state = &engine.snap.state;
+	// status on the first row, help on the last one
+	let view_h = rows.saturating_sub(2);
+	// hex cells take two columns, the odd rows shifted by one
+	let hex = level.topology == Topology::Hex;
+	let view_w = if hex {
+		cols.saturating_sub(1) / 2
+	} else {
+		cols
+	};
+	let x0 = scroll(state.px, view_w, level.w);
+	let y0 = scroll(state.py, view_h, level.h);
+	for j in y0..level.h.min(y0 + view_h) {
+		let shift = if hex { j % 2 } else { 0 };
+		queue!(out, MoveTo(shift as u16, (j - y0 + 1) as u16))?;
+		for i in x0..level.w.min(x0 + view_w) {
+			let e = state.map[j][i];
+			let player = state.workers.contains(&(i, j));
+			// the goals are the stores, or the crates start cells when pulling
+			let goal = engine.is_goal(i, j);
+			let glyph = if player {
+				G_PLAYER
+			} else if e == WALL {
+				G_WALL
+			} else if !level.inside[j][i] {
+				G_EMPTY
+			} else if engine.is_stored(i, j) {
+				G_STORED
+			} else if e & CRATE == CRATE {
+				G_CRATE
+			} else if goal {
+				G_STORE
+			} else {
+				G_EMPTY
+			};
+			let (xsb, uni, color) = GLYPHS[glyph];
+			let c = match (unicode, player && goal) {
+				(false, true) => C_SPLAYER,
+				(false, false) => xsb,
+				(true, _) => uni,
+			};
+			// the active worker stands out among several
+			let color = match color {
+				Color::Cyan if (i, j) != (state.px, state.py) => Color::DarkCyan,
+				_ => color,
+			};
+			let tint = match glyph {
+				G_CRATE | G_STORED => state.colors[j][i],
+				G_STORE => engine.goal_color(i, j),
+				_ => 0,
+			};
+			let color = match (tint, glyph) {
+				(0, _) => color,
+				(_, G_CRATE) => TINTS[tint as usize - 1].1,
+				_ => TINTS[tint as usize - 1].0,
+			};
+			let floor = glyph == G_EMPTY && level.inside[j][i];
+			let (c, color) = match level.topology {
+				_ if floor && level.tiles[j][i] != Tile::Floor => {
+					tile_glyph(level.tiles[j][i], unicode)
+				}
+				// the floor shows which way the triangles point
+				Topology::Triangle if unicode && floor => {
+					let c = if Topology::points_up(i, j) {
+						'▵'
+					} else {
+						'▿'
+					};
+					(c, Color::DarkGrey)
+				}
+				_ => (c, color),
+			};
+			queue!(out, SetForegroundColor(color), Print(c))?;
+			if hex {
+				queue!(out, Print(' '))?;
+			}
+		}
+	}
+	Ok(())
+}
+
+/// Level info, menu and keys shown while paused
+fn pause_lines(engine: &Engine, ui: &Ui) -> Vec<String> {
+	let level = &engine.levels[engine.level];
+	let mut lines = vec![
+		"*PAUSE*".to_string(),
+		format!(
+			"Level {}/{}: {}/{} crates stored",
+			engine.level + 1,
+			engine.levels.len(),
+			engine.snap.state.stored,
+			level.crates
+		),
+	];
+	if let Some(best) = engine.best_score() {
+		lines.push(format!(
+			"Best: {} moves, {} pushes, {}s",
+			best.moves, best.pushes, best.time_s
+		));
+	}
+	lines.push(String::new());
+	match ui.menu.settings {
+		Some(row) => {
+			let chars = if ui.unicode { "Unicode" } else { "XSB" };
+			lines.push(menu::row(row == 0, &format!("Characters < {} >", chars)));
+			lines.push(menu::row(row == 1, "Back"));
+		}
+		None => lines.extend(ui.menu.lines(engine.levels.len())),
+	}
+	lines.push(String::new());
+	lines.push(PAUSE_HELP.to_string());
+	lines
+}
+
+fn draw(out: &mut Stdout, engine: &Engine, ui: &Ui) -> Result<()> {
+	let (cols, rows) = size()?;
+	let (cols, rows) = (cols as usize, rows as usize);
+	queue!(out, Clear(ClearType::All))?;
+	if let Status::Pause = engine.status {
+		// the level stays hidden while the clock is stopped
+		for (j, line) in pause_lines(engine, ui).iter().enumerate() {
+			queue!(out, MoveTo(2, (j + 2) as u16), Print(line))?;
+		}
+	} else if ui.stats {
+		for (j, line) in stats::summary(&engine.history, engine.levels.len())
+			.iter()
+			.enumerate()
+		{
+			queue!(out, MoveTo(2, (j + 2) as u16), Print(line))?;
+		}
+	} else {
+		draw_level(out, engine, ui.unicode, cols, rows)?;
+	}
+	let hint = match engine.status {
+		Status::Win => "You win! Press Enter..",
+		Status::Pause => "*PAUSE* Press Space..",
+		Status::Pull => "PULL the crates back",
+		_ => "",
+	};
+	queue!(
+		out,
+		ResetColor,
+		MoveTo(0, 0),
+		Print(format!("{} {}", engine.summary(), hint)),
+		MoveTo(0, rows.saturating_sub(1) as u16),
+		Print(HELP),
+	)?;
+	out.flush()
+}
+
+fn pause(engine: &mut Engine, ui: &mut Ui) {
+	if engine.playing() {
+		engine.status = Status::Pause;
+		ui.menu = PauseMenu::new(engine.level);
+	}
+}
+
+/// Apply a key press to the pause menu; return false to quit
+fn handle_menu_key(engine: &mut Engine, key: KeyCode, ui: &mut Ui) -> bool {
+	let levels = engine.levels.len();
+	match (ui.menu.settings, key) {
+		(_, KeyCode::Up) => ui.menu.select(-1, SETTINGS_ROWS),
+		(_, KeyCode::Down) => ui.menu.select(1, SETTINGS_ROWS),
+		(Some(0), KeyCode::Left) | (Some(0), KeyCode::Right) | (Some(0), KeyCode::Enter) => {
+			ui.unicode = !ui.unicode
+		}
+		(Some(_), KeyCode::Enter) => ui.menu.settings = None,
+		(None, KeyCode::Left) => ui.menu.choose_level(-1, levels),
+		(None, KeyCode::Right) => ui.menu.choose_level(1, levels),
+		(None, KeyCode::Enter) => match ui.menu.entry() {
+			Entry::Resume => engine.resume(),
+			Entry::Restart => {
+				engine.set_level(engine.level);
+			}
+			Entry::Level => {
+				engine.set_level(ui.menu.level);
+			}
+			Entry::Settings => ui.menu.settings = Some(0),
+			Entry::Quit => return false,
+		},
+		_ => {}
+	}
+	true
+}
+
+/// Apply a key press; return false to quit
+fn handle_key(engine: &mut Engine, key: KeyEvent, ui: &mut Ui) -> bool {
+	match (&engine.status, key.code) {
+		(_, KeyCode::Char('q')) | (_, KeyCode::Esc) => return false,
+		(_, KeyCode::Char('c')) => ui.unicode = !ui.unicode,
+		(_, KeyCode::Char('i')) => ui.stats = !ui.stats,
+		_ if ui.stats => {}
+		(_, KeyCode::Char('r')) => {
+			engine.set_level(engine.level);
+		}
+		(_, KeyCode::Char('n')) | (_, KeyCode::PageDown) => {
+			engine.set_level(engine.level + 1);
+		}
+		(_, KeyCode::Char('p')) | (_, KeyCode::PageUp) => {
+			engine.set_level(engine.level.saturating_sub(1));
+		}
+		(_, KeyCode::Char('v')) => engine.set_reverse(!engine.reverse),
+		(Status::Play | Status::Pull, KeyCode::Char(' ')) => pause(engine, ui),
+		(Status::Pause, KeyCode::Char(' ')) => engine.resume(),
+		(Status::Pause, code) => return handle_menu_key(engine, code, ui),
+		(Status::Play | Status::Pull, KeyCode::Up) => {
+			engine.try_move(0, -1);
+		}
+		(Status::Play | Status::Pull, KeyCode::Down) => {
+			engine.try_move(0, 1);
+		}
+		(Status::Play | Status::Pull, KeyCode::Left) => {
+			engine.try_move(-1, 0);
+		}
+		(Status::Play | Status::Pull, KeyCode::Right) => {
+			engine.try_move(1, 0);
+		}
+		// diagonals of the hex and triangle grids, laid out as on a keypad
+		(Status::Play | Status::Pull, KeyCode::Char('7')) => {
+			engine.try_move(-1, -1);
+		}
+		(Status::Play | Status::Pull, KeyCode::Char('9')) => {
+			engine.try_move(1, -1);
+		}
+		(Status::Play | Status::Pull, KeyCode::Char('1')) => {
+			engine.try_move(-1, 1);
+		}
+		(Status::Play | Status::Pull, KeyCode::Char('3')) => {
+			engine.try_move(1, 1);
+		}
+		(Status::Play | Status::Pull, KeyCode::Tab) => {
+			engine.next_worker();
+		}
+		(Status::Play | Status::Pull, KeyCode::Char('u')) => {
+			engine.pop_undo();
+		}
+		(Status::Play | Status::Pull, KeyCode::Char('y')) => {
+			engine.pop_redo();
+		}
+		(Status::Play | Status::Pull, KeyCode::Char('s')) => engine.save_snapshot(),
+		(Status::Play | Status::Pull, KeyCode::Char('l')) => {
+			engine.load_snapshot();
+		}
+		(Status::Win, KeyCode::Enter) => return engine.set_level(engine.level + 1),
+		_ => {}
+	}
+	true
+}
+
+fn run(engine: &mut Engine) -> Result<()> {
+	let mut terminal = Terminal::new()?;
+	let mut ui = Ui {
+		unicode: false,
+		stats: false,
+		menu: PauseMenu::new(engine.level),
+	};
+	// terminals that don't report focus changes keep it true
+	let mut focused = true;
+	draw(&mut terminal.out, engine, &ui)?;
+	loop {
+		let mut must_draw = engine.tick(focused && !ui.stats);
+		if poll(Duration::from_millis(TICK_MS))? {
+			match read()? {
+				Event::Key(key) if key.kind == KeyEventKind::Press => {
+					if !handle_key(engine, key, &mut ui) {
+						return Ok(());
+					}
+					must_draw = true;
+				}
+				Event::Resize(..) => must_draw = true,
+				Event::FocusLost => {
+					focused = false;
+					pause(engine, &mut ui);
+					must_draw = true;
+				}
+				Event::FocusGained => focused = true,
+				_ => {}
+			}
+		}
+		if must_draw {
+			draw(&mut terminal.out, engine, &ui)?;
+		}
+	}
+}
+
+fn main() {
+	let levels = bundled_levels(&res_dir());
+	let scores_file = data_dir().join(SCORES_FILE);
+	let mut engine = Engine::new(levels, scores_file.to_str().unwrap());
+	run(&mut engine).expect("Terminal error");
+	engine.leave_attempt(Outcome::Left);
+	if let Status::Win = engine.status {
+		if engine.level + 1 == engine.levels.len() {
+			println!("Game over.");
+		}
+	}
+}
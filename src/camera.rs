@@ -0,0 +1,59 @@
+const SPEED: f32 = 0.2; // fraction of the remaining distance covered per frame
+const ZOOM_STEP: f32 = 1.25;
+
+/// Scrolling and zooming view over maps larger than the window
+pub struct Camera {
+	pub x: f32, // view origin in map pixels
+	pub y: f32, // view origin in map pixels
+	pub zoom: f32,
+}
+
+impl Default for Camera {
+	fn default() -> Self {
+		Camera {
+			x: 0.0,
+			y: 0.0,
+			zoom: 1.0,
+		}
+	}
+}
+
+/// Position centering `target` in `view`, clamped to the `size` of the map
+fn clamp(target: f32, view: f32, size: f32) -> f32 {
+	if size <= view {
+		0.0
+	} else {
+		(target - view / 2.0).max(0.0).min(size - view)
+	}
+}
+
+impl Camera {
+	/// Scroll towards `target` (map pixels), at once if `snap`; return true if the view moved
+	pub fn follow(
+		&mut self,
+		target: (f32, f32),
+		view: (f32, f32),
+		size: (f32, f32),
+		snap: bool,
+	) -> bool {
+		let tx = clamp(target.0, view.0, size.0);
+		let ty = clamp(target.1, view.1, size.1);
+		let (ox, oy) = (self.x, self.y);
+		if snap || ((tx - self.x).abs() < 0.5 && (ty - self.y).abs() < 0.5) {
+			self.x = tx;
+			self.y = ty;
+		} else {
+			self.x += (tx - self.x) * SPEED;
+			self.y += (ty - self.y) * SPEED;
+		}
+		ox.round() != self.x.round() || oy.round() != self.y.round()
+	}
+
+	pub fn zoom_in(&mut self) {
+		self.zoom *= ZOOM_STEP;
+	}
+
+	pub fn zoom_out(&mut self) {
+		self.zoom /= ZOOM_STEP;
+	}
+}
@@ -0,0 +1,172 @@
+use std::fs::read_to_string;
+use std::path::PathBuf;
+use std::process::exit;
+
+use cratesan::dirs::{config_dir, data_dir, res_dir};
+use cratesan::{assets, scores_file_name, valid_levels, Level, LEVELS_FILE, SCORES_FILE};
+
+use crate::{CONFIG_FILE, HEIGHT, WIDTH};
+
+const USAGE: &str = "Usage: cratesan [OPTIONS] [COMMAND]
+
+Options:
+  -l, --levels FILE     levels file (collection) to play
+  -n, --level N         start at level N
+  -s, --scores FILE     scores file
+  -r, --res DIR         resources directory
+  -g, --size WxH        window size
+  -f, --fullscreen      start in fullscreen
+  -d, --debug           print debug traces
+  -h, --help            print this help
+
+Commands:
+  solve [N]             print a LURD solution of each level, or of level N
+  verify N SOLUTION     replay a LURD solution (or a file holding it) on level N
+  optimize N SOLUTION   shorten a LURD solution (or a file holding it) of level N
+  lint                  check the levels for errors
+  convert [--xsb] [OUT] write the levels in the cratesan format, or in standard XSB
+  stats [--csv [OUT]]   summarize the progress, or export the history of the attempts";
+
+/// Non-interactive tools, run instead of the game
+pub enum Tool {
+	Solve(Option<usize>),
+	Verify(usize, String),
+	Optimize(usize, String),
+	Lint,
+	Convert(bool, Option<PathBuf>), // standard XSB, output file
+	Stats(bool, Option<PathBuf>),   // CSV export, output file
+}
+
+pub struct Options {
+	pub levels_file: Option<PathBuf>, // None for the bundled levels
+	pub scores_file: PathBuf,
+	pub config_file: PathBuf,
+	pub res_dir: PathBuf,
+	pub level: Option<usize>, // 0-based
+	pub width: usize,
+	pub height: usize,
+	pub fullscreen: bool,
+	pub debug: bool,
+	pub tool: Option<Tool>,
+}
+
+fn fail(message: &str) -> ! {
+	eprintln!("{}\n\n{}", message, USAGE);
+	exit(2);
+}
+
+fn level_number(value: &str) -> usize {
+	match value.parse::<usize>() {
+		Ok(n) if n > 0 => n - 1,
+		_ => fail(&format!("Invalid level number [{}]", value)),
+	}
+}
+
+fn window_size(value: &str) -> (usize, usize) {
+	let mut iter = value.split('x').map(|v| v.parse::<usize>());
+	match (iter.next(), iter.next(), iter.next()) {
+		(Some(Ok(w)), Some(Ok(h)), None) if w > 0 && h > 0 => (w, h),
+		_ => fail(&format!("Invalid window size [{}]", value)),
+	}
+}
+
+impl Options {
+	/// Parse the command line arguments, exiting with the usage on errors
+	pub fn parse(args: impl Iterator<Item = String>) -> Options {
+		let mut options = Options {
+			levels_file: None,
+			scores_file: data_dir().join(SCORES_FILE),
+			config_file: config_dir().join(CONFIG_FILE),
+			res_dir: res_dir(),
+			level: None,
+			width: WIDTH,
+			height: HEIGHT,
+			fullscreen: false,
+			debug: false,
+			tool: None,
+		};
+		let mut positionals = Vec::new();
+		let mut scores_file = None;
+		let mut xsb = false;
+		let mut csv = false;
+		let mut args = args.skip(1);
+		while let Some(arg) = args.next() {
+			let mut value = || {
+				args.next()
+					.unwrap_or_else(|| fail(&format!("Missing value for {}", arg)))
+			};
+			match arg.as_str() {
+				"-l" | "--levels" => options.levels_file = Some(PathBuf::from(value())),
+				"-n" | "--level" => options.level = Some(level_number(&value())),
+				"-s" | "--scores" => scores_file = Some(PathBuf::from(value())),
+				"-r" | "--res" => options.res_dir = PathBuf::from(value()),
+				"-g" | "--size" => {
+					let (width, height) = window_size(&value());
+					options.width = width;
+					options.height = height;
+				}
+				"-f" | "--fullscreen" => options.fullscreen = true,
+				"-d" | "--debug" => options.debug = true,
+				"--xsb" => xsb = true,
+				"--csv" => csv = true,
+				"-h" | "--help" => {
+					println!("{}", USAGE);
+					exit(0);
+				}
+				_ if arg.starts_with('-') => fail(&format!("Unknown option {}", arg)),
+				_ => positionals.push(arg),
+			}
+		}
+		// each levels file keeps its own scores, the bundled levels the plain ones
+		match (scores_file, &options.levels_file) {
+			(Some(scores_file), _) => options.scores_file = scores_file,
+			(None, Some(levels_file)) => {
+				options.scores_file = data_dir().join(scores_file_name(levels_file))
+			}
+			(None, None) => {}
+		}
+		let positionals: Vec<&str> = positionals.iter().map(String::as_str).collect();
+		options.tool = match positionals.as_slice() {
+			[] => None,
+			["solve"] => Some(Tool::Solve(None)),
+			["solve", n] => Some(Tool::Solve(Some(level_number(n)))),
+			["verify", n, solution] => Some(Tool::Verify(level_number(n), solution.to_string())),
+			["optimize", n, solution] => {
+				Some(Tool::Optimize(level_number(n), solution.to_string()))
+			}
+			["lint"] => Some(Tool::Lint),
+			["convert"] => Some(Tool::Convert(xsb, None)),
+			["convert", out] => Some(Tool::Convert(xsb, Some(PathBuf::from(out)))),
+			["stats"] => Some(Tool::Stats(csv, None)),
+			["stats", out] if csv => Some(Tool::Stats(csv, Some(PathBuf::from(out)))),
+			_ => fail(&format!("Invalid command [{}]", positionals.join(" "))),
+		};
+		if xsb && !matches!(options.tool, Some(Tool::Convert(..))) {
+			fail("--xsb only applies to convert");
+		}
+		if csv && !matches!(options.tool, Some(Tool::Stats(..))) {
+			fail("--csv only applies to stats");
+		}
+		options
+	}
+
+	/// Text of the levels file, or of the bundled levels
+	pub fn levels_text(&self) -> String {
+		let text = match &self.levels_file {
+			Some(file) => read_to_string(file).ok(),
+			None => assets::read(&self.res_dir, LEVELS_FILE)
+				.and_then(|bytes| String::from_utf8(bytes).ok()),
+		};
+		text.unwrap_or_else(|| {
+			let file = match &self.levels_file {
+				Some(file) => file.clone(),
+				None => self.res_dir.join(LEVELS_FILE),
+			};
+			panic!("Couldn't open the levels {}", file.display())
+		})
+	}
+
+	pub fn levels(&self) -> Vec<Level> {
+		valid_levels(&self.levels_text())
+	}
+}
@@ -0,0 +1,162 @@
+use crate::{Level, CRATE, STORE};
+
+/// Color of each cell, 0 for plain crates and goals, or the color number from 1
+pub type Colors = Vec<Vec<u8>>;
+
+/// Letter and name of each color, numbered from 1
+pub const COLORS: [(char, &str); 6] = [
+	('r', "red"),
+	('g', "green"),
+	('b', "blue"),
+	('y', "yellow"),
+	('c', "cyan"),
+	('m', "magenta"),
+];
+const PLAIN: (char, &str) = ('-', "plain");
+
+/// Color of the letter `c`, if valid
+pub fn parse(c: char) -> Option<u8> {
+	if c == PLAIN.0 {
+		return Some(0);
+	}
+	COLORS
+		.iter()
+		.position(|&(letter, _)| letter == c)
+		.map(|k| k as u8 + 1)
+}
+
+/// Letter and name of the color `color`
+fn named(color: u8) -> (char, &'static str) {
+	match color {
+		0 => PLAIN,
+		_ => COLORS[color as usize - 1],
+	}
+}
+
+/// Cells of `level` holding `element`, in reading order
+fn cells(level: &Level, element: u8) -> Vec<(usize, usize)> {
+	let mut cells = Vec::new();
+	for (j, line) in level.map.iter().enumerate() {
+		for (i, &e) in line.iter().enumerate() {
+			if e & element == element {
+				cells.push((i, j));
+			}
+		}
+	}
+	cells
+}
+
+/// Color the crates and the goals of `level` in reading order, one letter each, eg: `rgg`
+/// for a red crate and two green ones; the stored crates are the ones on a goal of their
+/// color
+pub fn paint(level: &mut Level, crates: Option<&str>, goals: Option<&str>) -> Result<(), String> {
+	for &(element, letters, name) in &[(CRATE, crates, "crate"), (STORE, goals, "goal")] {
+		let letters = match letters {
+			Some(letters) => letters,
+			None => continue,
+		};
+		let colors = letters
+			.chars()
+			.filter(|c| !c.is_whitespace())
+			.map(|c| parse(c).ok_or(format!("Invalid color [{}]", c)))
+			.collect::<Result<Vec<_>, _>>()?;
+		let cells = cells(level, element);
+		if colors.len() != cells.len() {
+			return Err(format!(
+				"Mismatch between {}s={} and {} colors={}",
+				name,
+				cells.len(),
+				name,
+				colors.len()
+			));
+		}
+		let layer = if element == CRATE {
+			&mut level.crate_colors
+		} else {
+			&mut level.goal_colors
+		};
+		for (&(x, y), color) in cells.iter().zip(colors) {
+			layer[y][x] = color;
+		}
+	}
+	level.stored = cells(level, CRATE | STORE)
+		.iter()
+		.filter(|&&(x, y)| level.crate_colors[y][x] == level.goal_colors[y][x])
+		.count() as u32;
+	Ok(())
+}
+
+/// Whether some crate or goal is colored
+pub fn any(level: &Level) -> bool {
+	level
+		.crate_colors
+		.iter()
+		.chain(&level.goal_colors)
+		.flatten()
+		.any(|&c| c != 0)
+}
+
+/// Comments coloring the crates and the goals of `level`, as `paint` takes them, empty if
+/// they are all plain
+pub fn comments(level: &Level) -> String {
+	if !any(level) {
+		return String::new();
+	}
+	let letters = |element: u8, layer: &Colors| -> String {
+		cells(level, element)
+			.iter()
+			.map(|&(x, y)| named(layer[y][x]).0)
+			.collect()
+	};
+	format!(
+		"; Crates: {}\n; Goals: {}\n",
+		letters(CRATE, &level.crate_colors),
+		letters(STORE, &level.goal_colors)
+	)
+}
+
+/// Colors whose crates and goals don't pair up, as "2 red crates for 1 red goals"
+pub fn mismatches(level: &Level) -> Vec<String> {
+	let count = |element: u8, layer: &Colors, color: u8| {
+		cells(level, element)
+			.iter()
+			.filter(|&&(x, y)| layer[y][x] == color)
+			.count()
+	};
+	(0..=COLORS.len() as u8)
+		.filter_map(|color| {
+			let crates = count(CRATE, &level.crate_colors, color);
+			let goals = count(STORE, &level.goal_colors, color);
+			if crates == goals {
+				return None;
+			}
+			let name = named(color).1;
+			Some(format!(
+				"{} {} crates for {} {} goals",
+				crates, name, goals, name
+			))
+		})
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::valid_levels;
+
+	#[test]
+	fn mismatches_count_each_color() {
+		let map = "######\n#@$$.#\n#  . #\n######\n";
+		let level = &valid_levels(&format!("{}; Crates: rr\n; Goals: rg\n", map))[0];
+		assert_eq!(
+			mismatches(level),
+			vec![
+				"2 red crates for 1 red goals",
+				"0 green crates for 1 green goals"
+			]
+		);
+		let level = &valid_levels(&format!("{}; Crates: rg\n; Goals: gr\n", map))[0];
+		assert!(mismatches(level).is_empty());
+		assert!(mismatches(&valid_levels(map)[0]).is_empty());
+	}
+}
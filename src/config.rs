@@ -0,0 +1,364 @@
+use sdl2::controller::Button;
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+
+use crate::skin::DEFAULT_SKIN;
+
+use std::fs::File;
+use std::io::Read;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Action {
+	Up,
+	Down,
+	Left,
+	Right,
+	UpLeft, // hex and triangle grids diagonals
+	UpRight,
+	DownLeft,
+	DownRight,
+	Worker,
+	Undo,
+	Redo,
+	Save,
+	Load,
+	Restart,
+	Reverse,
+	Win,
+	Pause,
+	Next,
+	Debug,
+	Help,
+	Stats,
+	Fullscreen,
+	Minimap,
+	ZoomIn,
+	ZoomOut,
+	Skin,
+	VolumeUp,
+	VolumeDown,
+	Mute,
+	Quit,
+}
+
+/// Action, config name, help text, default keys and default controller buttons
+#[allow(clippy::type_complexity)]
+const ACTIONS: [(Action, &str, &str, &[&str], &[&str]); 30] = [
+	(Action::Up, "up", "move up", &["Up"], &["dpup"]),
+	(Action::Down, "down", "move down", &["Down"], &["dpdown"]),
+	(Action::Left, "left", "move left", &["Left"], &["dpleft"]),
+	(
+		Action::Right,
+		"right",
+		"move right",
+		&["Right"],
+		&["dpright"],
+	),
+	(
+		Action::UpLeft,
+		"up_left",
+		"move up left",
+		&["Q", "Keypad 7"],
+		&[],
+	),
+	(
+		Action::UpRight,
+		"up_right",
+		"move up right",
+		&["E", "Keypad 9"],
+		&[],
+	),
+	(
+		Action::DownLeft,
+		"down_left",
+		"move down left",
+		&["Z", "Keypad 1"],
+		&[],
+	),
+	(
+		Action::DownRight,
+		"down_right",
+		"move down right",
+		&["C", "Keypad 3"],
+		&[],
+	),
+	(Action::Worker, "worker", "switch worker", &["Tab"], &["x"]),
+	(Action::Undo, "undo", "undo last move", &["U"], &["b"]),
+	(Action::Redo, "redo", "redo last undo", &["Y"], &["y"]),
+	(
+		Action::Save,
+		"save",
+		"save snapshot",
+		&["S"],
+		&["leftshoulder"],
+	),
+	(
+		Action::Load,
+		"load",
+		"load snapshot",
+		&["L"],
+		&["rightshoulder"],
+	),
+	(
+		Action::Restart,
+		"restart",
+		"restart level",
+		&["R"],
+		&["back"],
+	),
+	(
+		Action::Reverse,
+		"pull",
+		"toggle pull (reverse) mode",
+		&["V"],
+		&[],
+	),
+	(Action::Win, "win", "skip level", &["W"], &[]),
+	(
+		Action::Pause,
+		"pause",
+		"pause/resume",
+		&["Space"],
+		&["start"],
+	),
+	(
+		Action::Next,
+		"next",
+		"next level when won",
+		&["Return"],
+		&["a"],
+	),
+	(Action::Debug, "debug", "toggle debug", &["D"], &[]),
+	(
+		Action::Help,
+		"help",
+		"toggle this help",
+		&["F1"],
+		&["guide"],
+	),
+	(Action::Stats, "stats", "toggle statistics", &["F2"], &[]),
+	(
+		Action::Fullscreen,
+		"fullscreen",
+		"toggle fullscreen",
+		&["F11"],
+		&[],
+	),
+	(Action::Minimap, "minimap", "toggle minimap", &["M"], &[]),
+	(Action::ZoomIn, "zoom_in", "zoom in", &["="], &[]),
+	(Action::ZoomOut, "zoom_out", "zoom out", &["-"], &[]),
+	(Action::Skin, "skin", "switch skin", &["T"], &[]),
+	(Action::VolumeUp, "volume_up", "volume up", &["]"], &[]),
+	(
+		Action::VolumeDown,
+		"volume_down",
+		"volume down",
+		&["["],
+		&[],
+	),
+	(Action::Mute, "mute", "toggle mute", &["N"], &[]),
+	(Action::Quit, "quit", "quit", &["Escape"], &[]),
+];
+
+/// Prefix of the config names binding controller buttons, eg: `pad_undo = b, x`
+const PAD_PREFIX: &str = "pad_";
+
+pub struct Config {
+	bindings: Vec<(Action, Vec<Keycode>, Vec<Button>)>,
+	pub anim_ms: u32, // duration of move animations, 0 to disable them
+	pub skin: String,
+	pub volume: u8, // percent
+	pub mute: bool,
+}
+
+fn parse<T: FromStr>(name: &str, value: &str, config_file: &str) -> T {
+	value.parse().unwrap_or_else(|_| {
+		panic!(
+			"Invalid value [{}] for {} in config file {}",
+			value, name, config_file
+		)
+	})
+}
+
+impl Default for Config {
+	fn default() -> Self {
+		Config {
+			bindings: ACTIONS
+				.iter()
+				.map(|&(action, _, _, keys, buttons)| {
+					(
+						action,
+						keys.iter().filter_map(|k| Keycode::from_name(k)).collect(),
+						buttons
+							.iter()
+							.filter_map(|b| Button::from_string(b))
+							.collect(),
+					)
+				})
+				.collect(),
+			anim_ms: 100,
+			skin: DEFAULT_SKIN.to_string(),
+			volume: 80,
+			mute: false,
+		}
+	}
+}
+
+impl Config {
+	/// Load the config file, made of `name = value` lines; a missing file gives the defaults
+	pub fn load(config_file: &str) -> Config {
+		let mut config = Config::default();
+		let mut sconfig = String::new();
+		if let Ok(mut f) = File::open(config_file) {
+			f.read_to_string(&mut sconfig).unwrap();
+		}
+		for line in sconfig.lines() {
+			let line = line.trim();
+			if line.is_empty() || line.starts_with('#') {
+				continue;
+			}
+			let (name, value) = match line.find('=') {
+				Some(i) => (line[..i].trim(), line[i + 1..].trim()),
+				None => panic!("Invalid line [{}] in config file {}", line, config_file),
+			};
+			let values = value.split(',').map(str::trim).filter(|v| !v.is_empty());
+			if let Some(&(action, _, _, _, _)) = ACTIONS.iter().find(|a| a.1 == name) {
+				let keys = values
+					.map(|k| {
+						Keycode::from_name(k).unwrap_or_else(|| {
+							panic!("Invalid key [{}] in config file {}", k, config_file)
+						})
+					})
+					.collect();
+				for binding in config.bindings.iter_mut() {
+					if binding.0 == action {
+						binding.1 = keys;
+						break;
+					}
+				}
+			} else if let Some(&(action, _, _, _, _)) = ACTIONS
+				.iter()
+				.find(|a| name.starts_with(PAD_PREFIX) && a.1 == &name[PAD_PREFIX.len()..])
+			{
+				let buttons = values
+					.map(|b| {
+						Button::from_string(b).unwrap_or_else(|| {
+							panic!("Invalid button [{}] in config file {}", b, config_file)
+						})
+					})
+					.collect();
+				for binding in config.bindings.iter_mut() {
+					if binding.0 == action {
+						binding.2 = buttons;
+						break;
+					}
+				}
+			} else if name == "anim_ms" {
+				config.anim_ms = parse(name, value, config_file);
+			} else if name == "skin" {
+				config.skin = value.to_string();
+			} else if name == "volume" {
+				config.volume = parse(name, value, config_file);
+			} else if name == "mute" {
+				config.mute = parse(name, value, config_file);
+			} else {
+				panic!("Invalid setting [{}] in config file {}", name, config_file);
+			}
+		}
+		config
+	}
+
+	/// Map an input event to the action bound to it, if any
+	pub fn action(&self, event: &Event) -> Option<Action> {
+		match event {
+			Event::KeyDown {
+				keycode: Some(k), ..
+			} => self
+				.bindings
+				.iter()
+				.find(|(_, keys, _)| keys.contains(k))
+				.map(|b| b.0),
+			Event::ControllerButtonDown { button, .. } => self
+				.bindings
+				.iter()
+				.find(|(_, _, buttons)| buttons.contains(button))
+				.map(|b| b.0),
+			_ => None,
+		}
+	}
+
+	/// One line per action with its active keys
+	pub fn help(&self) -> Vec<String> {
+		ACTIONS
+			.iter()
+			.zip(&self.bindings)
+			.map(|(&(_, _, text, _, _), (_, keys, buttons))| {
+				let keys: Vec<String> = keys
+					.iter()
+					.map(|k| k.name())
+					.chain(buttons.iter().map(|b| format!("pad {}", b.string())))
+					.collect();
+				format!("{:<20} {}", text, keys.join(", "))
+			})
+			.collect()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::env::temp_dir;
+	use std::fs::{remove_file, write};
+	use std::process;
+
+	fn load(name: &str, text: &str) -> Config {
+		let file = temp_dir().join(format!("cratesan-{}-{}.txt", name, process::id()));
+		write(&file, text).unwrap();
+		let config = Config::load(file.to_str().unwrap());
+		remove_file(&file).unwrap();
+		config
+	}
+
+	fn binding(config: &Config, action: Action) -> &(Action, Vec<Keycode>, Vec<Button>) {
+		config.bindings.iter().find(|b| b.0 == action).unwrap()
+	}
+
+	#[test]
+	fn load_reads_the_settings_and_bindings() {
+		let config = load(
+			"settings",
+			"# comment\n\nundo = Backspace, U\npad_undo = a\nanim_ms = 0\nskin = retro\nvolume = 50\nmute = true\n",
+		);
+		assert_eq!(
+			binding(&config, Action::Undo).1,
+			vec![Keycode::Backspace, Keycode::U]
+		);
+		assert_eq!(binding(&config, Action::Undo).2, vec![Button::A]);
+		assert_eq!(binding(&config, Action::Redo).1, vec![Keycode::Y]);
+		assert_eq!(config.anim_ms, 0);
+		assert_eq!(config.skin, "retro");
+		assert_eq!(config.volume, 50);
+		assert!(config.mute);
+	}
+
+	#[test]
+	fn missing_file_gives_the_defaults() {
+		let config = Config::load("/nonexistent/cratesan.cfg");
+		assert_eq!(config.anim_ms, 100);
+		assert_eq!(config.skin, DEFAULT_SKIN);
+		assert_eq!(binding(&config, Action::Undo).2, vec![Button::B]);
+	}
+
+	#[test]
+	#[should_panic(expected = "Invalid key [Nope]")]
+	fn load_rejects_unknown_keys() {
+		load("keys", "undo = Nope\n");
+	}
+
+	#[test]
+	#[should_panic(expected = "Invalid setting [speed]")]
+	fn load_rejects_unknown_settings() {
+		load("names", "speed = 3\n");
+	}
+}
@@ -0,0 +1,72 @@
+use std::env::{current_exe, var_os};
+use std::path::PathBuf;
+
+use crate::LEVELS_FILE;
+
+const APP_DIR: &str = "cratesan";
+/// Environment variable naming the resources directory, at run time or at build time
+const RES_VAR: &str = "CRATESAN_RES";
+/// Resources directory compiled in, overridden by CRATESAN_RES when building
+const BUILTIN_RES_DIR: &str = match option_env!("CRATESAN_RES") {
+	Some(dir) => dir,
+	None => concat!(env!("CARGO_MANIFEST_DIR"), "/res"),
+};
+
+/// Absolute path held by the environment variable `name`, if any
+fn env_dir(name: &str) -> Option<PathBuf> {
+	var_os(name)
+		.map(PathBuf::from)
+		.filter(|dir| dir.is_absolute())
+}
+
+fn exe_dir() -> Option<PathBuf> {
+	current_exe()
+		.ok()
+		.and_then(|exe| exe.parent().map(|dir| dir.to_path_buf()))
+}
+
+/// XDG base directory from `xdg_var`, defaulting to `home_default` under $HOME,
+/// then to the executable directory
+fn xdg_dir(xdg_var: &str, home_default: &str) -> PathBuf {
+	env_dir(xdg_var)
+		.or_else(|| env_dir("HOME").map(|home| home.join(home_default)))
+		.or_else(exe_dir)
+		.unwrap_or_default()
+}
+
+/// Directories searched for the resources, in order: $CRATESAN_RES, `res` next to
+/// the executable, `cratesan` in the XDG data directories, then the compiled-in path
+pub fn res_search_path() -> Vec<PathBuf> {
+	let mut dirs = Vec::new();
+	dirs.extend(env_dir(RES_VAR));
+	dirs.extend(exe_dir().map(|dir| dir.join("res")));
+	dirs.push(data_dir());
+	let data_dirs = var_os("XDG_DATA_DIRS")
+		.filter(|dirs| !dirs.is_empty())
+		.unwrap_or_else(|| "/usr/local/share:/usr/share".into());
+	dirs.extend(
+		std::env::split_paths(&data_dirs)
+			.filter(|dir| dir.is_absolute())
+			.map(|dir| dir.join(APP_DIR)),
+	);
+	dirs.push(PathBuf::from(BUILTIN_RES_DIR));
+	dirs
+}
+
+/// First directory of the search path holding the levels, or the compiled-in path
+pub fn res_dir() -> PathBuf {
+	res_search_path()
+		.into_iter()
+		.find(|dir| dir.join(LEVELS_FILE).is_file())
+		.unwrap_or_else(|| PathBuf::from(BUILTIN_RES_DIR))
+}
+
+/// Directory of the scores and other saved data: $XDG_DATA_HOME/cratesan
+pub fn data_dir() -> PathBuf {
+	xdg_dir("XDG_DATA_HOME", ".local/share").join(APP_DIR)
+}
+
+/// Directory of the settings: $XDG_CONFIG_HOME/cratesan
+pub fn config_dir() -> PathBuf {
+	xdg_dir("XDG_CONFIG_HOME", ".config").join(APP_DIR)
+}
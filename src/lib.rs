@@ -0,0 +1,1260 @@
+pub mod assets;
+pub mod color;
+pub mod dirs;
+pub mod menu;
+pub mod optimizer;
+pub mod path;
+pub mod solver;
+pub mod stats;
+pub mod tile;
+pub mod topology;
+
+use std::fs::{create_dir_all, File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use color::Colors;
+use stats::{Attempt, Outcome};
+use tile::{Tile, Tiles};
+use topology::Topology;
+
+pub const VERSION: u8 = 1;
+pub const EMPTY: u8 = 0x0;
+pub const STORE: u8 = 0x1;
+pub const CRATE: u8 = 0x2;
+pub const WALL: u8 = 0x4;
+pub const C_EMPTY: char = ' ';
+pub const C_STORE: char = '.';
+pub const C_STORED: char = '*';
+pub const C_CRATE: char = '$';
+pub const C_PLAYER: char = '@';
+pub const C_SPLAYER: char = '&';
+pub const C_WALL: char = '#';
+pub const C_XSB_SPLAYER: char = '+'; // standard XSB player on a store
+pub const C_XSB_EMPTY: [char; 2] = ['-', '_']; // standard XSB floor alternatives
+pub const LEVELS_FILE: &str = "levels/levels.txt"; // in the resources directory
+pub const SCORES_FILE: &str = "scores.txt";
+const COLLECTION_SCORES: &str = "scores-"; // prefix of the scores of a levels file
+pub const PULL_SCORES_FILE: &str = "pull_scores.txt"; // a data file, see data_file
+pub const SOLUTIONS_FILE: &str = "solutions.txt"; // a data file, see data_file
+
+pub type Map = Vec<Vec<u8>>;
+
+pub enum Status {
+	Play,
+	Pull, // playing in reverse mode
+	Pause,
+	Win,
+}
+
+#[derive(Clone, Default)]
+pub struct Level {
+	pub crates: u32,
+	pub w: usize,
+	pub h: usize,
+	pub map: Map,
+	pub inside: Vec<Vec<bool>>, // interior cells, enclosed by walls along with the player
+	pub stored: u32,            // crates on a goal of their color
+	pub px: usize,              // first worker
+	pub py: usize,
+	pub workers: Vec<(usize, usize)>, // all the workers, in reading order
+	pub tiles: Tiles,                 // special floors, under the map
+	pub crate_colors: Colors,         // crates colors at the start
+	pub goal_colors: Colors,
+	pub title: String, // first comment after the map, empty if none
+	pub topology: Topology,
+}
+
+#[derive(Debug)]
+pub struct Score {
+	pub level: u16,
+	pub moves: u16,
+	pub pushes: u16,
+	pub time_s: u32,
+}
+
+#[derive(Default)]
+pub struct Snapshot {
+	pub state: State,
+	pub undo_states: Vec<State>,
+	pub redo_states: Vec<State>,
+}
+
+#[derive(Default, Clone)]
+pub struct State {
+	pub map: Map,
+	pub moves: i32,
+	pub pushes: i32,
+	pub time_ms: u64, // play time
+	pub stored: u32,
+	pub colors: Colors, // crates colors, moving along with them
+	pub px: usize,      // active worker
+	pub py: usize,
+	pub dir: i32, // player direction (0:W 1:N: 2:E 3:S)
+	pub undos: u32,
+	pub workers: Vec<(usize, usize)>, // all the workers, the active one at px:py
+	pub worker: usize,                // active worker
+	pub lurd: String,                 // extended LURD of the moves so far, on square grids
+}
+
+/// What a successful move did, for the frontends feedback
+#[derive(Clone, Copy, PartialEq)]
+pub enum Moved {
+	Step,
+	Push,
+	Store, // a push that stored one more crate
+	Win,
+}
+
+/// Parse one level of the XSB text `s`
+pub fn parse_level(s: &str) -> Result<Level, String> {
+	parse_level_in(Topology::Square, s)
+}
+
+/// Parse one level of the text `s`, its cells laid out on a `topology` grid
+pub fn parse_level_in(topology: Topology, s: &str) -> Result<Level, String> {
+	let mut lev = Level {
+		topology,
+		..Default::default()
+	};
+	let mut stores = 0;
+	for line in s.lines() {
+		if line.chars().count() > lev.w {
+			lev.w = line.chars().count();
+		}
+	}
+	for line in s.lines() {
+		if line.is_empty() {
+			continue;
+		}
+		let mut v = vec![EMPTY; lev.w];
+		let mut tiles = vec![Tile::Floor; lev.w];
+		for (i, e) in line.chars().enumerate() {
+			match e {
+				C_EMPTY => {
+					v[i] = EMPTY;
+				}
+				_ if C_XSB_EMPTY.contains(&e) => {
+					v[i] = EMPTY;
+				}
+				C_STORE => {
+					v[i] = STORE;
+					stores += 1;
+				}
+				C_CRATE => {
+					v[i] = CRATE;
+					lev.crates += 1;
+				}
+				C_STORED => {
+					v[i] = CRATE | STORE;
+					stores += 1;
+					lev.crates += 1;
+					lev.stored += 1;
+				}
+				C_PLAYER => {
+					lev.workers.push((i, lev.h));
+					v[i] = EMPTY;
+				}
+				C_SPLAYER | C_XSB_SPLAYER => {
+					lev.workers.push((i, lev.h));
+					v[i] = STORE;
+					stores += 1;
+				}
+				C_WALL => {
+					v[i] = WALL;
+				}
+				_ => match tile::parse(e) {
+					Some(t) => tiles[i] = t,
+					None => return Err(format!("Invalid element [{}]", e)),
+				},
+			}
+		}
+		lev.map.push(v);
+		lev.tiles.push(tiles);
+		lev.h += 1;
+	}
+	if lev.crates != stores {
+		return Err(format!(
+			"Mismatch between crates={} and stores={}",
+			lev.crates, stores
+		));
+	}
+	tile::check(&lev.tiles)?;
+	lev.crate_colors = vec![vec![0; lev.w]; lev.h];
+	lev.goal_colors = lev.crate_colors.clone();
+	match lev.workers.first() {
+		Some(&(px, py)) => {
+			lev.px = px;
+			lev.py = py;
+		}
+		None => return Err("Player not found".to_string()),
+	}
+	lev.inside = vec![vec![false; lev.w]; lev.h];
+	for &worker in &lev.workers {
+		let inside = path::interior(topology, &lev.map, worker);
+		for (line, worker_line) in lev.inside.iter_mut().zip(inside) {
+			for (e, worker_e) in line.iter_mut().zip(worker_line) {
+				*e |= worker_e;
+			}
+		}
+	}
+	Ok(lev)
+}
+
+/// Parse the levels of a collection, separated by empty lines; the first `;` comment
+/// following a level is its title, eg: `; Title: Corner` or `; 12`, a
+/// `; Topology: hex` or `; Topology: triangle` comment sets its grid, square by default,
+/// and `; Crates: rg` and `; Goals: gr` comments color its crates and goals
+pub fn parse_levels(slevels: &str) -> Vec<Result<Level, String>> {
+	let mut vlevels = Vec::new();
+	let mut slevel = String::new();
+	let mut title = None;
+	let mut topology = Ok(Topology::Square);
+	let mut colors = (None, None);
+	for line in slevels.lines() {
+		if line.is_empty() {
+			if !slevel.is_empty() {
+				vlevels.push((slevel, title.take(), topology, colors));
+				slevel = "".to_string();
+				topology = Ok(Topology::Square);
+				colors = (None, None);
+			}
+			continue;
+		}
+		if let Some(comment) = line.strip_prefix(';') {
+			// comments before a level, eg: the collection header, are skipped
+			let comment = comment.trim();
+			if slevel.is_empty() {
+				continue;
+			}
+			if let Some(name) = comment.strip_prefix("Topology:") {
+				let name = name.trim();
+				topology = Topology::parse(name).ok_or(format!("Invalid topology [{}]", name));
+			} else if let Some(letters) = comment.strip_prefix("Crates:") {
+				colors.0 = Some(letters.to_string());
+			} else if let Some(letters) = comment.strip_prefix("Goals:") {
+				colors.1 = Some(letters.to_string());
+			} else if title.is_none() {
+				title = Some(
+					comment
+						.strip_prefix("Title:")
+						.unwrap_or(comment)
+						.trim()
+						.to_string(),
+				);
+			}
+			continue;
+		}
+		slevel = format!("{}\n{}", slevel, line);
+	}
+	if !slevel.is_empty() {
+		vlevels.push((slevel, title, topology, colors));
+	}
+	vlevels
+		.into_iter()
+		.map(|(s, title, topology, (crates, goals))| {
+			let mut level = parse_level_in(topology?, &s)?;
+			color::paint(&mut level, crates.as_deref(), goals.as_deref())?;
+			level.title = title.unwrap_or_default();
+			Ok(level)
+		})
+		.collect()
+}
+
+/// Parse the levels of a collection, panicking on the first invalid level
+pub fn valid_levels(slevels: &str) -> Vec<Level> {
+	parse_levels(slevels)
+		.into_iter()
+		.enumerate()
+		.map(|(i, level)| level.unwrap_or_else(|e| panic!("{} in level {}", e, i + 1)))
+		.collect()
+}
+
+/// Scores file name of the levels file `levels_file`, eg: scores-extra.txt for extra.txt
+pub fn scores_file_name(levels_file: &Path) -> String {
+	let stem = levels_file
+		.file_stem()
+		.and_then(|s| s.to_str())
+		.unwrap_or_default();
+	format!("{}{}.txt", COLLECTION_SCORES, stem)
+}
+
+/// Data file `name` kept along with `scores_file`, for the same levels file, eg:
+/// pull_scores-extra.txt along with scores-extra.txt
+pub fn data_file(scores_file: &Path, name: &str) -> PathBuf {
+	let stem = scores_file
+		.file_stem()
+		.and_then(|s| s.to_str())
+		.unwrap_or_default();
+	let name = match stem.strip_prefix(COLLECTION_SCORES) {
+		Some(collection) => {
+			let (base, ext) = name.split_at(name.rfind('.').unwrap_or(name.len()));
+			format!("{}-{}{}", base, collection, ext)
+		}
+		None => name.to_string(),
+	};
+	scores_file.with_file_name(name)
+}
+
+/// Load the bundled levels from `res_dir`, or their embedded copy
+pub fn bundled_levels(res_dir: &Path) -> Vec<Level> {
+	let slevels = assets::read(res_dir, LEVELS_FILE)
+		.and_then(|bytes| String::from_utf8(bytes).ok())
+		.unwrap_or_else(|| {
+			panic!(
+				"Couldn't open the levels {}",
+				res_dir.join(LEVELS_FILE).display()
+			)
+		});
+	valid_levels(&slevels)
+}
+
+/// Play time as h:mm:ss
+pub fn hms(time_ms: u64) -> String {
+	let time_s = time_ms / 1000;
+	format!(
+		"{}:{:02}:{:02}",
+		time_s / 3600,
+		(time_s / 60) % 60,
+		time_s % 60
+	)
+}
+
+/// Write a level back as text, using the standard XSB player on store if `xsb`
+pub fn level_text(level: &Level, xsb: bool) -> String {
+	let mut text = String::new();
+	for (j, line) in level.map.iter().enumerate() {
+		let mut sline = String::new();
+		for (i, &e) in line.iter().enumerate() {
+			let player = level.workers.contains(&(i, j));
+			sline.push(match e {
+				STORE if player && xsb => C_XSB_SPLAYER,
+				STORE if player => C_SPLAYER,
+				_ if player => C_PLAYER,
+				STORE => C_STORE,
+				CRATE => C_CRATE,
+				x if x == CRATE | STORE => C_STORED,
+				WALL => C_WALL,
+				_ if level.tiles[j][i] != Tile::Floor => tile::to_char(level.tiles[j][i]),
+				_ => C_EMPTY,
+			});
+		}
+		text.push_str(sline.trim_end());
+		text.push('\n');
+	}
+	if !level.title.is_empty() {
+		text.push_str(&format!("; {}\n", level.title));
+	}
+	if level.topology != Topology::Square {
+		text.push_str(&format!("; Topology: {}\n", level.topology.name()));
+	}
+	text.push_str(&color::comments(level));
+	text
+}
+
+/// Levels, scores and game state, shared by the SDL and terminal frontends
+pub struct Engine {
+	pub status: Status,
+	pub debug: bool,
+	// Game levels
+	pub levels: Vec<Level>,
+	pub level: usize,
+	// Game states
+	pub snapshots: Vec<Snapshot>,
+	pub snap: Snapshot,
+	last_tick: Instant, // play time counted up to then
+	pub scores: Vec<Score>,
+	scores_file: Option<String>, // None to play without saving scores
+	pub reverse: bool,           // pull mode: crates start on the stores, to be pulled back
+	pub pull_scores: Vec<Score>,
+	pull_scores_file: Option<String>,
+	pub history: Vec<Attempt>,
+	stats_file: Option<PathBuf>,     // saved along with the scores
+	solutions_file: Option<PathBuf>, // winning moves, saved along with the scores
+}
+
+impl Engine {
+	/// Play `levels`, loading the scores and the history of the attempts, starting at the
+	/// first unsolved level
+	pub fn new(levels: Vec<Level>, scores_file: &str) -> Engine {
+		let scores = Engine::load_scores(scores_file);
+		let mut e = Engine::with_levels(levels, scores, Some(scores_file.to_string()));
+		let stats_file = stats::stats_file(Path::new(scores_file));
+		e.history = stats::load(&stats_file);
+		e.stats_file = Some(stats_file);
+		let pull_scores_file = data_file(Path::new(scores_file), PULL_SCORES_FILE);
+		let pull_scores_file = pull_scores_file.to_str().unwrap().to_string();
+		e.pull_scores = Engine::load_scores(&pull_scores_file);
+		e.pull_scores_file = Some(pull_scores_file);
+		e.solutions_file = Some(data_file(Path::new(scores_file), SOLUTIONS_FILE));
+		e
+	}
+
+	/// Play `levels` without loading nor saving scores, eg: to replay solutions
+	pub fn offline(levels: Vec<Level>) -> Engine {
+		Engine::with_levels(levels, Vec::new(), None)
+	}
+
+	fn with_levels(levels: Vec<Level>, scores: Vec<Score>, scores_file: Option<String>) -> Engine {
+		let mut level = 0;
+		let mut dones = vec![false; levels.len()];
+		for score in &scores {
+			if (score.level as usize) < levels.len() {
+				dones[score.level as usize] = true;
+			}
+		}
+		for done in dones {
+			if !done {
+				break;
+			}
+			level += 1;
+		}
+
+		let mut e = Engine {
+			status: Status::Play,
+			debug: false,
+			levels,
+			snapshots: Vec::new(),
+			snap: Default::default(),
+			level,
+			last_tick: Instant::now(),
+			scores,
+			scores_file,
+			history: Vec::new(),
+			stats_file: None,
+			reverse: false,
+			pull_scores: Vec::new(),
+			pull_scores_file: None,
+			solutions_file: None,
+		};
+		e.set_level(level);
+		e
+	}
+
+	pub fn debug_dump(&self) {
+		if self.debug {
+			println!(
+				"level={} crates={}/{} moves={} pushes={} undos={}/{} snaps={} time={}",
+				self.level + 1,
+				self.snap.state.moves,
+				self.snap.state.pushes,
+				self.snap.state.time_ms / 1000,
+				self.snap.state.stored,
+				self.levels[self.level].crates,
+				self.snapshots.len(),
+				self.snap.state.undos,
+				self.snap.undo_states.len(),
+			);
+		}
+	}
+
+	fn save_state(&self, state: &mut State, full: bool) {
+		*state = State {
+			map: Vec::new(),
+			colors: Vec::new(),
+			stored: self.snap.state.stored,
+			px: self.snap.state.px,
+			py: self.snap.state.py,
+			dir: self.snap.state.dir,
+			time_ms: self.snap.state.time_ms,
+			pushes: self.snap.state.pushes,
+			moves: self.snap.state.moves,
+			undos: self.snap.state.undos,
+			workers: self.snap.state.workers.clone(),
+			worker: self.snap.state.worker,
+			lurd: self.snap.state.lurd.clone(),
+		};
+		if full {
+			state.map = self.snap.state.map.clone();
+			state.colors = self.snap.state.colors.clone();
+		}
+	}
+
+	fn restore_state(&mut self, state_: State) {
+		let map = self.snap.state.map.clone();
+		let colors = self.snap.state.colors.clone();
+		let time_ms = self.snap.state.time_ms;
+		self.snap.state = state_;
+		if self.snap.state.map.is_empty() {
+			self.snap.state.map = map;
+			self.snap.state.colors = colors;
+		}
+		// undos, redos and snapshots don't rewind the play time
+		self.snap.state.time_ms = time_ms;
+	}
+
+	pub fn save_snapshot(&mut self) {
+		self.snapshots.clear(); // limit snapshots depth to 1
+		let mut snap = Snapshot {
+			undo_states: self.snap.undo_states.clone(),
+			redo_states: self.snap.redo_states.clone(),
+			state: Default::default(),
+		};
+		self.save_state(&mut snap.state, true);
+		self.snapshots.push(snap);
+		self.debug_dump();
+	}
+
+	/// Restore the saved snapshot, if any; return true if the state changed
+	pub fn load_snapshot(&mut self) -> bool {
+		if let Some(snap) = self.snapshots.pop() {
+			self.snap.undo_states = snap.undo_states;
+			self.snap.redo_states = snap.redo_states;
+			self.restore_state(snap.state);
+			self.save_scores();
+			self.save_snapshot(); // limit snapshots depth to 1
+			true
+		} else {
+			false
+		}
+	}
+
+	fn save_score(&mut self) {
+		let score = Score {
+			level: self.level as u16,
+			pushes: self.snap.state.pushes as u16,
+			moves: self.snap.state.moves as u16,
+			time_s: (self.snap.state.time_ms / 1000) as u32,
+		};
+		let level = self.level;
+		let scores = if self.reverse {
+			&mut self.pull_scores
+		} else {
+			&mut self.scores
+		};
+		if !scores.iter().any(|score| score.level as usize == level) {
+			scores.push(score);
+		}
+	}
+
+	fn save_scores(&self) {
+		Engine::write_scores(&self.scores, &self.scores_file);
+		Engine::write_scores(&self.pull_scores, &self.pull_scores_file);
+	}
+
+	fn write_scores(scores: &[Score], scores_file: &Option<String>) {
+		if let (false, Some(scores_file)) = (scores.is_empty(), scores_file) {
+			if let Some(dir) = Path::new(scores_file).parent() {
+				create_dir_all(dir).unwrap();
+			}
+			let mut file = File::create(scores_file).unwrap();
+			writeln!(file, "{}", VERSION).unwrap();
+			writeln!(file, "{}", scores.len()).unwrap();
+			for s in scores {
+				writeln!(file, "{} {} {} {}", s.level, s.pushes, s.moves, s.time_s).unwrap();
+			}
+		}
+	}
+
+	fn load_scores(scores_file: &str) -> Vec<Score> {
+		macro_rules! scan {
+			($string:expr, $sep:expr, $( $x:ty ),+ ) => {{
+				let mut iter = $string.split($sep);
+				($(iter.next().and_then(|word| word.parse::<$x>().ok()).unwrap(),)*)
+			}}
+		}
+		let mut version = 0;
+		let mut nscores = 0;
+		let mut ret = Vec::new();
+		if let Ok(file) = File::open(scores_file) {
+			let mut reader = BufReader::new(file);
+			let mut line = String::new();
+			if let Ok(_nbytes) = reader.read_line(&mut line) {
+				version = scan!(line, char::is_whitespace, u8).0;
+			}
+			if version != VERSION {
+				panic!(
+					"Invalid scores version. Please delete the scores file {}.",
+					&scores_file
+				);
+			}
+			line.clear();
+			if let Ok(_nbytes) = reader.read_line(&mut line) {
+				nscores = scan!(line, char::is_whitespace, u8).0;
+			}
+			let mut sscores = String::new();
+			reader.read_to_string(&mut sscores).unwrap();
+			for line in sscores.lines() {
+				let (level, pushes, moves, time_s) =
+					scan!(line, char::is_whitespace, u16, u16, u16, u32);
+				ret.push(Score {
+					level,
+					pushes,
+					moves,
+					time_s,
+				});
+			}
+			if nscores as usize != ret.len() {
+				panic!(
+					"Invalid number of scores (read {} parsed {}). Please delete the scores file {}.",
+					nscores, ret.len(), &scores_file
+				);
+			}
+		}
+		ret
+	}
+
+	/// Undo the last move, if any; return true if the state changed
+	pub fn pop_undo(&mut self) -> bool {
+		if let Some(state) = self.snap.undo_states.pop() {
+			let mut redo = Default::default();
+			self.save_state(&mut redo, !state.map.is_empty());
+			self.snap.redo_states.push(redo);
+			self.restore_state(state);
+			self.save_scores();
+			self.snap.state.undos += 1;
+			self.debug_dump();
+			true
+		} else {
+			false
+		}
+	}
+
+	fn push_undo(&mut self, full: bool) {
+		let mut s = Default::default();
+		self.save_state(&mut s, full);
+		self.snap.undo_states.push(s);
+		self.snap.redo_states.clear();
+	}
+
+	/// Redo the last undone move, if any; return true if the state changed
+	pub fn pop_redo(&mut self) -> bool {
+		if let Some(state) = self.snap.redo_states.pop() {
+			let mut undo = Default::default();
+			self.save_state(&mut undo, !state.map.is_empty());
+			self.snap.undo_states.push(undo);
+			let undos = self.snap.state.undos;
+			self.restore_state(state);
+			self.snap.state.undos = undos;
+			self.save_scores();
+			self.debug_dump();
+			true
+		} else {
+			false
+		}
+	}
+
+	pub fn set_level(&mut self, level: usize) -> bool {
+		if level < self.levels.len() {
+			self.leave_attempt(if level == self.level {
+				Outcome::Restarted
+			} else {
+				Outcome::Left
+			});
+			self.start_level(level);
+			true
+		} else {
+			false
+		}
+	}
+
+	fn start_level(&mut self, level: usize) {
+		self.status = if self.reverse {
+			Status::Pull
+		} else {
+			Status::Play
+		};
+		self.level = level;
+		self.snap.state.moves = 0;
+		self.snap.state.pushes = 0;
+		self.snap.state.time_ms = 0;
+		self.snap.state.undos = 0;
+		self.last_tick = Instant::now();
+		self.snap.state.map = self.levels[level].map.clone();
+		self.snap.state.colors = self.levels[level].crate_colors.clone();
+		self.snap.undo_states = Vec::new();
+		self.snap.redo_states = Vec::new();
+		self.snap.state.stored = self.levels[level].stored;
+		self.levels[self.level].w = self.levels[level].w;
+		self.levels[self.level].h = self.levels[level].h;
+		self.snap.state.px = self.levels[level].px;
+		self.snap.state.py = self.levels[level].py;
+		self.snap.state.workers = self.levels[level].workers.clone();
+		self.snap.state.worker = 0;
+		self.snap.state.lurd.clear();
+		if self.reverse {
+			self.reverse_start();
+		}
+	}
+
+	/// Switch between the normal and pull modes, restarting the level
+	pub fn set_reverse(&mut self, reverse: bool) {
+		self.leave_attempt(Outcome::Restarted);
+		self.reverse = reverse;
+		self.snapshots.clear();
+		self.start_level(self.level);
+	}
+
+	/// Put the crates on the stores, of their colors, and the workers on the closest free
+	/// cells if crates took their places
+	fn reverse_start(&mut self) {
+		let level = &self.levels[self.level];
+		let map = &mut self.snap.state.map;
+		for line in map.iter_mut() {
+			for e in line.iter_mut() {
+				if *e & STORE == STORE {
+					*e |= CRATE;
+				} else {
+					*e &= !CRATE;
+				}
+			}
+		}
+		self.snap.state.colors = level.goal_colors.clone();
+		let workers = &mut self.snap.state.workers;
+		for k in 0..workers.len() {
+			let (px, py) = workers[k];
+			if map[py][px] & CRATE != CRATE {
+				continue;
+			}
+			let mut free = Vec::new();
+			for (j, line) in map.iter().enumerate() {
+				for (i, &e) in line.iter().enumerate() {
+					if level.inside[j][i] && e & (WALL | CRATE) == 0 && !workers.contains(&(i, j)) {
+						free.push((i, j));
+					}
+				}
+			}
+			let distance = |&(i, j): &(usize, usize)| {
+				(i as isize - px as isize).abs() + (j as isize - py as isize).abs()
+			};
+			if let Some(&cell) = free.iter().min_by_key(|c| distance(c)) {
+				workers[k] = cell;
+			}
+		}
+		let (px, py) = workers[0];
+		self.snap.state.px = px;
+		self.snap.state.py = py;
+		// the targets are the crates start cells, some crates may already be there
+		self.snap.state.stored = level.stored;
+	}
+
+	/// Whether x:y is where a crate must end: a store, or a crate start cell when pulling
+	pub fn is_goal(&self, x: usize, y: usize) -> bool {
+		if self.reverse {
+			self.levels[self.level].map[y][x] & CRATE == CRATE
+		} else {
+			self.snap.state.map[y][x] & STORE == STORE
+		}
+	}
+
+	/// Color of the crates that `is_goal` x:y takes
+	pub fn goal_color(&self, x: usize, y: usize) -> u8 {
+		let level = &self.levels[self.level];
+		if self.reverse {
+			level.crate_colors[y][x]
+		} else {
+			level.goal_colors[y][x]
+		}
+	}
+
+	/// Whether a crate is stored at x:y: on a goal of its color
+	pub fn is_stored(&self, x: usize, y: usize) -> bool {
+		self.snap.state.map[y][x] & CRATE == CRATE
+			&& self.is_goal(x, y)
+			&& self.snap.state.colors[y][x] == self.goal_color(x, y)
+	}
+
+	/// Whether the player can move: playing, in either mode
+	pub fn playing(&self) -> bool {
+		matches!(self.status, Status::Play | Status::Pull)
+	}
+
+	/// Resume playing after a pause, in the current mode
+	pub fn resume(&mut self) {
+		self.status = if self.reverse {
+			Status::Pull
+		} else {
+			Status::Play
+		};
+	}
+
+	/// Count the play time while `running` and playing; return true if the seconds shown
+	/// changed
+	pub fn tick(&mut self, running: bool) -> bool {
+		let now = Instant::now();
+		let elapsed = now.duration_since(self.last_tick).as_millis() as u64;
+		if !running || !self.playing() {
+			self.last_tick = now;
+			return false;
+		}
+		let before = self.snap.state.time_ms / 1000;
+		self.snap.state.time_ms += elapsed;
+		// keep the sub-millisecond remainder for the next tick
+		self.last_tick += Duration::from_millis(elapsed);
+		self.snap.state.time_ms / 1000 != before
+	}
+
+	/// Play time as h:mm:ss
+	pub fn clock(&self) -> String {
+		hms(self.snap.state.time_ms)
+	}
+
+	/// Add the current attempt to the history; pull mode attempts aren't recorded, to keep
+	/// the statistics comparable
+	fn record_attempt(&mut self, outcome: Outcome) {
+		if self.reverse {
+			return;
+		}
+		let attempt = Attempt {
+			date: stats::now(),
+			level: self.level,
+			outcome,
+			moves: self.snap.state.moves,
+			pushes: self.snap.state.pushes,
+			undos: self.snap.state.undos,
+			time_ms: self.snap.state.time_ms,
+		};
+		if let Some(stats_file) = &self.stats_file {
+			stats::append(stats_file, &attempt);
+			self.history.push(attempt);
+		}
+	}
+
+	/// End the current attempt if it was started and not won, the wins being recorded
+	/// as they happen; the frontends call it when quitting
+	pub fn leave_attempt(&mut self, outcome: Outcome) {
+		let started = self.snap.state.moves > 0 || self.snap.state.undos > 0;
+		if started && !matches!(self.status, Status::Win) {
+			self.record_attempt(outcome);
+		}
+	}
+
+	/// Score saved for the current level and mode, if it was solved before
+	pub fn best_score(&self) -> Option<&Score> {
+		let scores = if self.reverse {
+			&self.pull_scores
+		} else {
+			&self.scores
+		};
+		scores.iter().find(|s| s.level as usize == self.level)
+	}
+
+	/// Level number, moves, pushes and time, as shown in the status bars, then the active
+	/// worker if there are several
+	pub fn summary(&self) -> String {
+		let mut summary = format!(
+			"{:02}| moves: {:04} pushes: {:04} time:{}",
+			self.level + 1,
+			self.snap.state.moves,
+			self.snap.state.pushes,
+			self.clock(),
+		);
+		let workers = self.snap.state.workers.len();
+		if workers > 1 {
+			summary.push_str(&format!(
+				" worker: {}/{}",
+				self.snap.state.worker + 1,
+				workers
+			));
+		}
+		summary
+	}
+
+	fn can_move(&self, x: usize, y: usize) -> bool {
+		if x < self.levels[self.level].w && y < self.levels[self.level].h {
+			let e = self.snap.state.map[y][x];
+			if (e == EMPTY || e == STORE) && !self.snap.state.workers.contains(&(x, y)) {
+				return true;
+			}
+		}
+		false
+	}
+
+	/// Map to walk the active worker on, the other workers standing as walls, and the
+	/// special tiles too, their moves being out of the path searches
+	pub fn walk_map(&self) -> Map {
+		let mut map = self.snap.state.map.clone();
+		for (k, &(x, y)) in self.snap.state.workers.iter().enumerate() {
+			if k != self.snap.state.worker {
+				map[y][x] = WALL;
+			}
+		}
+		if !self.reverse {
+			for (line, tiles) in map.iter_mut().zip(&self.levels[self.level].tiles) {
+				for (e, &t) in line.iter_mut().zip(tiles) {
+					if t != Tile::Floor {
+						*e = WALL;
+					}
+				}
+			}
+		}
+		map
+	}
+
+	/// Make `worker` the active worker; return true if it changed
+	pub fn switch_worker(&mut self, worker: usize) -> bool {
+		if worker >= self.snap.state.workers.len() || worker == self.snap.state.worker {
+			return false;
+		}
+		self.snap.state.worker = worker;
+		let (px, py) = self.snap.state.workers[worker];
+		self.snap.state.px = px;
+		self.snap.state.py = py;
+		true
+	}
+
+	/// Make the next worker, in reading order, the active one; return true if it changed
+	pub fn next_worker(&mut self) -> bool {
+		let next = (self.snap.state.worker + 1) % self.snap.state.workers.len();
+		self.switch_worker(next)
+	}
+
+	/// Try to move one cell the dx:dy way, eg: to x+dx:y+dy on a square grid, and also
+	/// push a crate there one more cell the same way; nothing moves unless playing
+	pub fn try_move(&mut self, dx: isize, dy: isize) -> Option<Moved> {
+		if !self.playing() {
+			return None;
+		}
+		if self.reverse {
+			return self.try_pull(dx, dy);
+		}
+		let mut do_it = false;
+		let mut moved = Moved::Step;
+		let topology = self.levels[self.level].topology;
+		let (px, py) = (self.snap.state.px, self.snap.state.py);
+		let (x, y) = topology.step(&self.snap.state.map, px, py, dx, dy)?;
+		if !self.enters(x, y, dx, dy) {
+			return None;
+		}
+		if self.snap.state.map[y][x] & CRATE == CRATE {
+			let to = topology
+				.step(&self.snap.state.map, x, y, dx, dy)
+				.filter(|&(to_x, to_y)| {
+					self.can_move(to_x, to_y) && self.enters(to_x, to_y, dx, dy)
+				});
+			if let Some((to_x, to_y)) = to {
+				let (to_x, to_y) = self.crate_rest(to_x, to_y, dx, dy);
+				do_it = true;
+				moved = Moved::Push;
+				self.push_undo(true);
+				self.snap.state.pushes += 1;
+				if self.move_crate((x, y), (to_x, to_y)) {
+					moved = Moved::Store;
+					if self.snap.state.stored == self.levels[self.level].crates {
+						self.status = Status::Win;
+					}
+				}
+			}
+		} else {
+			do_it = self.can_move(x, y);
+			if do_it {
+				self.push_undo(false);
+			}
+		}
+		if !do_it {
+			return None;
+		}
+		let (x, y) = self.teleport(x, y);
+		Some(self.finish_move(x, y, dx, dy, moved))
+	}
+
+	/// Move the crate at x:y to `to`, along with its color, counting the stored crates;
+	/// return whether their count increased, not when moving from a goal to another
+	fn move_crate(&mut self, (x, y): (usize, usize), (to_x, to_y): (usize, usize)) -> bool {
+		let before = self.snap.state.stored;
+		if self.is_stored(x, y) {
+			self.snap.state.stored -= 1;
+		}
+		let state = &mut self.snap.state;
+		state.map[y][x] &= !CRATE;
+		state.map[to_y][to_x] |= CRATE;
+		state.colors[to_y][to_x] = state.colors[y][x];
+		if self.is_stored(to_x, to_y) {
+			self.snap.state.stored += 1;
+		}
+		self.snap.state.stored > before
+	}
+
+	/// Whether the player or a crate may enter x:y moving dx:dy, as one-way floors allow
+	fn enters(&self, x: usize, y: usize, dx: isize, dy: isize) -> bool {
+		match self.levels[self.level].tiles[y][x] {
+			Tile::OneWay(wx, wy) => (wx, wy) == (dx, dy),
+			_ => true,
+		}
+	}
+
+	/// Where a crate pushed to x:y moving dx:dy stops: further on ice until blocked, then
+	/// out of a teleporter
+	fn crate_rest(&self, x: usize, y: usize, dx: isize, dy: isize) -> (usize, usize) {
+		let level = &self.levels[self.level];
+		let (mut x, mut y) = (x, y);
+		while level.tiles[y][x] == Tile::Ice {
+			let next = level
+				.topology
+				.step(&self.snap.state.map, x, y, dx, dy)
+				.filter(|&(nx, ny)| self.can_move(nx, ny) && self.enters(nx, ny, dx, dy));
+			match next {
+				Some((nx, ny)) => {
+					x = nx;
+					y = ny;
+				}
+				None => break,
+			}
+		}
+		self.teleport(x, y)
+	}
+
+	/// Other end of the teleporter at x:y if it is free, else x:y
+	fn teleport(&self, x: usize, y: usize) -> (usize, usize) {
+		tile::twin(&self.levels[self.level].tiles, x, y)
+			.filter(|&(tx, ty)| self.can_move(tx, ty))
+			.unwrap_or((x, y))
+	}
+
+	/// Pull mode move: step away, pulling along the crate behind the player if any; the
+	/// special tiles are plain floors when pulling
+	fn try_pull(&mut self, dx: isize, dy: isize) -> Option<Moved> {
+		let topology = self.levels[self.level].topology;
+		let (px, py) = (self.snap.state.px, self.snap.state.py);
+		let (x, y) = topology.step(&self.snap.state.map, px, py, dx, dy)?;
+		if !self.can_move(x, y) {
+			return None;
+		}
+		let behind = topology
+			.step(&self.snap.state.map, px, py, -dx, -dy)
+			.filter(|&(bx, by)| self.snap.state.map[by][bx] & CRATE == CRATE);
+		let mut moved = Moved::Step;
+		match behind {
+			Some((bx, by)) => {
+				moved = Moved::Push;
+				self.push_undo(true);
+				self.snap.state.pushes += 1;
+				if self.move_crate((bx, by), (px, py)) {
+					moved = Moved::Store;
+				}
+			}
+			None => self.push_undo(false),
+		}
+		// won once the crates are back, with the workers able to walk to their starts
+		let level = &self.levels[self.level];
+		let map = &self.snap.state.map;
+		let workers = &self.snap.state.workers;
+		let back = level.workers.iter().enumerate().all(|(k, &(sx, sy))| {
+			let from = if k == self.snap.state.worker {
+				(x, y)
+			} else {
+				workers[k]
+			};
+			path::reachable(topology, map, from)[sy][sx]
+		});
+		if self.snap.state.stored == level.crates && back {
+			self.status = Status::Win;
+		}
+		Some(self.finish_move(x, y, dx, dy, moved))
+	}
+
+	/// Move the player to x:y after a push, pull or step, scoring a win
+	fn finish_move(&mut self, x: usize, y: usize, dx: isize, dy: isize, moved: Moved) -> Moved {
+		let mut moved = moved;
+		self.snap.state.moves += 1;
+		self.snap.state.px = x;
+		self.snap.state.py = y;
+		let worker = self.snap.state.worker;
+		self.snap.state.workers[worker] = (x, y);
+		if self.levels[self.level].topology == Topology::Square {
+			let lurd = &mut self.snap.state.lurd;
+			if solver::last_worker(lurd) != worker {
+				lurd.push_str(&solver::worker_token(worker));
+			}
+			lurd.push(solver::lurd(dx, dy, moved != Moved::Step));
+		}
+		self.snap.state.dir = 0;
+		match dx {
+			-1 => {
+				self.snap.state.dir = 0;
+			}
+			1 => {
+				self.snap.state.dir = 2;
+			}
+			_ => {}
+		}
+		match dy {
+			-1 => {
+				self.snap.state.dir = 1;
+			}
+			1 => {
+				self.snap.state.dir = 3;
+			}
+			_ => {}
+		}
+		if let Status::Win = self.status {
+			moved = Moved::Win;
+			self.save_score();
+			self.save_scores();
+			self.save_solution();
+			self.record_attempt(Outcome::Won);
+		}
+		self.debug_dump();
+		moved
+	}
+
+	/// Add the winning moves at the end of the solutions file, as `solve` prints them;
+	/// pulls don't solve the level, and LURD only exists on square grids
+	fn save_solution(&self) {
+		let state = &self.snap.state;
+		if self.reverse || state.lurd.is_empty() {
+			return;
+		}
+		if let Some(solutions_file) = &self.solutions_file {
+			if let Some(dir) = solutions_file.parent() {
+				create_dir_all(dir).unwrap();
+			}
+			let mut f = OpenOptions::new()
+				.create(true)
+				.append(true)
+				.open(solutions_file)
+				.unwrap();
+			writeln!(
+				f,
+				"Level {}: {} ({} moves, {} pushes)",
+				self.level + 1,
+				state.lurd,
+				state.moves,
+				state.pushes
+			)
+			.unwrap();
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::thread::sleep;
+
+	fn player(engine: &Engine) -> (usize, usize) {
+		(engine.snap.state.px, engine.snap.state.py)
+	}
+
+	fn engine(text: &str) -> Engine {
+		Engine::offline(valid_levels(text))
+	}
+
+	#[test]
+	fn data_files_follow_the_levels_file() {
+		let scores = Path::new("/data").join(scores_file_name(Path::new("/levels/extra.txt")));
+		assert_eq!(scores, Path::new("/data/scores-extra.txt"));
+		assert_eq!(
+			data_file(&scores, PULL_SCORES_FILE),
+			Path::new("/data/pull_scores-extra.txt")
+		);
+		let scores = Path::new("/data").join(SCORES_FILE);
+		assert_eq!(
+			data_file(&scores, SOLUTIONS_FILE),
+			Path::new("/data/solutions.txt")
+		);
+	}
+
+	#[test]
+	fn push_between_goals_is_no_store() {
+		let mut e = engine("#######\n#@*.  #\n#  $  #\n#######\n");
+		assert!(matches!(e.try_move(1, 0), Some(Moved::Push)));
+		assert_eq!(e.snap.state.stored, 1);
+	}
+
+	#[test]
+	fn push_stores_and_wins() {
+		let mut e = engine("#######\n#@$ . #\n#######\n");
+		assert!(matches!(e.try_move(1, 0), Some(Moved::Push)));
+		assert!(matches!(e.try_move(1, 0), Some(Moved::Win)));
+		assert!(matches!(e.status, Status::Win));
+		assert!(e.try_move(1, 0).is_none());
+		assert_eq!(e.snap.state.lurd, "RR");
+	}
+
+	#[test]
+	fn undo_restores_the_crates() {
+		let mut e = engine("#######\n#@$ . #\n#######\n");
+		e.try_move(1, 0);
+		assert!(e.pop_undo());
+		assert_eq!(player(&e), (1, 1));
+		assert_eq!(e.snap.state.map, e.levels[0].map);
+		assert!(e.pop_redo());
+		assert_eq!(player(&e), (2, 1));
+	}
+
+	#[test]
+	fn tick_stops_while_paused() {
+		let mut e = engine("#####\n#@$.#\n#####\n");
+		e.status = Status::Pause;
+		sleep(Duration::from_millis(20));
+		assert!(!e.tick(true));
+		assert_eq!(e.snap.state.time_ms, 0);
+		e.resume();
+		sleep(Duration::from_millis(20));
+		e.tick(true);
+		assert!(e.snap.state.time_ms >= 20);
+	}
+
+	#[test]
+	fn pull_mode_wins_with_the_crates_back() {
+		let mut e = engine("#####\n#@$.#\n#####\n");
+		e.set_reverse(true);
+		assert_eq!(e.snap.state.map[1][3], CRATE | STORE);
+		assert!(matches!(e.try_move(1, 0), Some(Moved::Step)));
+		assert!(matches!(e.try_move(-1, 0), Some(Moved::Win)));
+	}
+
+	#[test]
+	fn workers_switch_and_block_each_other() {
+		let mut e = engine("#######\n#@ @$.#\n#######\n");
+		assert!(e.try_move(1, 0).is_some());
+		assert!(e.try_move(1, 0).is_none());
+		assert!(e.next_worker());
+		assert!(matches!(e.try_move(1, 0), Some(Moved::Win)));
+		assert_eq!(e.snap.state.lurd, "r[2]R");
+	}
+
+	#[test]
+	fn crates_slide_on_ice() {
+		let mut e = engine("#########\n#@$~~~ .#\n#########\n");
+		e.try_move(1, 0);
+		assert_eq!(e.snap.state.map[1][6], CRATE);
+		assert_eq!(player(&e), (2, 1));
+	}
+
+	#[test]
+	fn one_way_floors_are_entered_one_way() {
+		let mut e = engine("########\n#@ < $.#\n########\n");
+		assert!(e.try_move(1, 0).is_some());
+		assert!(e.try_move(1, 0).is_none());
+		let mut e = engine("########\n#@ > $.#\n########\n");
+		for _ in 0..3 {
+			assert!(e.try_move(1, 0).is_some());
+		}
+		assert!(e.try_move(-1, 0).is_none());
+	}
+
+	#[test]
+	fn teleporters_move_the_player_and_crates() {
+		let mut e = engine("#########\n#@ 1  1 #\n#  $ .  #\n#########\n");
+		e.try_move(1, 0);
+		e.try_move(1, 0);
+		assert_eq!(player(&e), (6, 1));
+		// the crate comes out of the twin, which then is no way out for the player
+		let mut e = engine("#########\n#@$1  1.#\n#########\n");
+		e.try_move(1, 0);
+		assert_eq!(e.snap.state.map[1][6], CRATE);
+		e.try_move(1, 0);
+		assert_eq!(player(&e), (3, 1));
+		assert!(parse_levels("#####\n#@1$.#\n#####\n")[0].is_err());
+	}
+
+	#[test]
+	fn colored_crates_store_on_their_goals() {
+		let map = "######\n#@$ .#\n#*   #\n######\n";
+		assert!(parse_levels(&format!("{}; Crates: r\n", map))[0].is_err());
+		let mut e = engine(&format!("{}; Crates: rg\n; Goals: gr\n", map));
+		assert_eq!(e.snap.state.stored, 0);
+		e.try_move(1, 0);
+		assert!(matches!(e.try_move(1, 0), Some(Moved::Push)));
+		assert_eq!(e.snap.state.stored, 0);
+		assert!(matches!(e.status, Status::Play));
+		let text = format!("{}; Crates: rg\n; Goals: rg\n", map);
+		let mut e = engine(&text);
+		e.try_move(1, 0);
+		assert!(matches!(e.try_move(1, 0), Some(Moved::Win)));
+		assert_eq!(level_text(&e.levels[0], false), text);
+	}
+}
@@ -1,40 +1,70 @@
 extern crate sdl2;
 
-use sdl2::event::Event;
-use sdl2::image::{InitFlag, LoadTexture};
-use sdl2::keyboard::Keycode;
+mod audio;
+mod camera;
+mod cli;
+mod config;
+mod skin;
+mod tools;
+
+use audio::{Audio, Sound};
+use camera::Camera;
+use cli::Options;
+use config::{Action, Config};
+use cratesan::menu::{self, Entry, PauseMenu};
+use cratesan::stats::{self, Outcome};
+use cratesan::tile::Tile;
+use cratesan::topology::Topology;
+use cratesan::{assets, path, Engine, Moved, Status, CRATE, EMPTY, STORE, WALL};
+use skin::{Skin, WALL_E, WALL_N, WALL_S, WALL_W};
+
+use sdl2::controller::GameController;
+use sdl2::event::{Event, WindowEvent};
+use sdl2::image::InitFlag;
+use sdl2::mouse::MouseButton;
 use sdl2::pixels::Color;
-use sdl2::rect::Rect;
-use sdl2::render::{Canvas, Texture, TextureCreator};
-use sdl2::video::{Window, WindowContext};
+use sdl2::rect::{Point, Rect};
+use sdl2::render::{BlendMode, Canvas, Texture, TextureCreator};
+use sdl2::rwops::RWops;
+use sdl2::video::{FullscreenType, Window, WindowContext};
+use sdl2::GameControllerSubsystem;
 
-use std::env::current_exe;
-use std::fs::File;
-use std::io::{BufRead, BufReader, Read, Write};
+use std::collections::VecDeque;
 use std::thread::sleep;
-use std::time::{Duration, SystemTime};
+use std::time::{Duration, Instant};
 
 const TITLE: &str = "クレートさん Rust";
-const VERSION: u8 = 1;
 const ZOOM: usize = 2;
 const TEXT_SIZE: usize = 8;
 const TEXT_RATIO: usize = ZOOM;
 const WIDTH: usize = 320 * ZOOM;
 const HEIGHT: usize = 200 * ZOOM;
-const EMPTY: u8 = 0x0;
-const STORE: u8 = 0x1;
-const CRATE: u8 = 0x2;
-const WALL: u8 = 0x4;
-const C_EMPTY: char = ' ';
-const C_STORE: char = '.';
-const C_STORED: char = '*';
-const C_CRATE: char = '$';
-const C_PLAYER: char = '@';
-const C_SPLAYER: char = '&';
-const C_WALL: char = '#';
+const MIN_BW: usize = 16; // below that, levels scroll
+const MAX_BW: usize = 128;
+const MINIMAP_RATIO: usize = 4; // minimap fits in a quarter of the window
+const PANEL_RATIO: f32 = 1.7; // windows at least that wide show the HUD on a side panel
+const PANEL_WIDTH: usize = 96 * ZOOM;
+const CRATE_SCALE: f32 = 0.7; // crates and player size in the cells drawn as shapes
+const PLAYER_SCALE: f32 = 0.5;
+const TINTS: [(u8, u8, u8); 6] = [
+	(255, 64, 64),
+	(64, 255, 64),
+	(64, 128, 255),
+	(255, 255, 64),
+	(64, 255, 255),
+	(255, 64, 255),
+]; // colored crates and goals, in color::COLORS order
+const TELEPORTER_COLORS: [(u8, u8, u8); 5] = [
+	(255, 0, 255),
+	(0, 255, 255),
+	(255, 128, 0),
+	(128, 255, 0),
+	(255, 64, 64),
+]; // frames of the teleporter pairs, cycled through
+const FLASH_MS: u128 = 300;
+const MESSAGE_MS: u128 = 2000;
 const FONT_FILE: &str = "RobotoMono-Regular.ttf";
-const LEVELS_FILE: &str = "levels.txt";
-const SCORES_FILE: &str = "scores.txt";
+const CONFIG_FILE: &str = "config.txt";
 const I_EMPTY: &str = "empty.png";
 const I_STORE: &str = "store.png";
 const I_STORED: &str = "stored.png";
@@ -61,492 +91,514 @@ const N_PLAYERE: usize = 9;
 const N_SPLAYERE: usize = 10;
 const N_PLAYERS: usize = 11;
 const N_SPLAYERS: usize = 12;
-const N_WHITE: usize = 13;
 
-type Map = Vec<Vec<u8>>;
-
-enum Status {
-	Play,
-	Pause,
-	Win,
+/// Player commands, queued while a move is animated
+enum Command {
+	Move(isize, isize),
+	Worker,        // switch to the next worker
+	Switch(usize), // switch to that worker
+	Undo,
+	Redo,
+	Save, // snapshot, once the moves queued before are done
 }
 
-#[derive(Default)]
-struct Level {
-	crates: u32,
-	w: usize,
-	h: usize,
-	map: Map,
-	stored: u32,
-	px: usize,
-	py: usize,
+/// Rows of the settings page of the pause menu
+#[derive(Clone, Copy)]
+enum Setting {
+	Skin,
+	Volume,
+	Sound,
+	Fullscreen,
+	Back,
 }
 
-#[derive(Debug)]
-struct Score {
-	level: u16,
-	moves: u16,
-	pushes: u16,
-	time_s: u32,
-}
+const SETTINGS: [Setting; 5] = [
+	Setting::Skin,
+	Setting::Volume,
+	Setting::Sound,
+	Setting::Fullscreen,
+	Setting::Back,
+];
 
-#[derive(Default)]
-struct Snapshot {
-	state: State,
-	undo_states: Vec<State>,
-}
-
-#[derive(Default, Clone)]
-struct State {
-	map: Map,
-	moves: i32,
-	pushes: i32,
-	time_s: u32,
-	stored: u32,
-	px: usize,
-	py: usize,
-	dir: i32, // player direction (0:W 1:N: 2:E 3:S)
-	undos: u32,
+/// Player, and possibly pushed crate, sliding from one cell to another
+struct Anim {
+	player: ((usize, usize), (usize, usize)),
+	crate_: Option<((usize, usize), (usize, usize))>,
+	start: Instant,
 }
 
 struct Game<'ttf> {
 	// Game flags and status
 	quit: bool,
-	status: Status,
 	must_draw: bool,
-	debug: bool,
-	// Game levels
-	levels: Vec<Level>,
-	level: usize,
-	// Game states
-	snapshots: Vec<Snapshot>,
-	snap: Snapshot,
-	last_ticks: SystemTime,
-	scores: Vec<Score>,
-	scores_file: String,
+	help: bool,
+	stats: bool,
+	focused: bool, // the clock stops while the window is in the background
+	menu: PauseMenu,
+	config: Config,
+	controllers: Vec<GameController>,
+	audio: Audio,
+	// Game levels and states
+	engine: Engine,
+	flash: Option<(usize, usize, Instant)>, // unreachable cell feedback
+	drag: Option<(usize, usize, i32, i32)>, // dragged crate cell and mouse position
+	message: Option<(String, Instant)>,
+	anim: Option<Anim>,
+	commands: VecDeque<Command>,
 	// SDL stuff
 	width: usize,
 	height: usize,
 	bw: usize, // block dims
 	bh: usize, // block dims
+	ox: usize, // map origin
+	oy: usize, // map origin
+	camera: Camera,
+	minimap: bool,
+	pixel_ratio: f32,     // drawable size / window size, for hi-DPI
+	tile: (usize, usize), // skin tiles size
+	skins: Vec<String>,
+	skin: usize,
 	// TTF stuff
 	font: sdl2::ttf::Font<'ttf, 'static>,
 }
 
 impl<'ttf> Game<'ttf> {
-	fn debug_dump(&self) {
-		if self.debug {
-			println!(
-				"level={} crates={}/{} moves={} pushes={} undos={}/{} snaps={} time={}",
-				self.level + 1,
-				self.snap.state.moves,
-				self.snap.state.pushes,
-				self.snap.state.time_s,
-				self.snap.state.stored,
-				self.levels[self.level].crates,
-				self.snapshots.len(),
-				self.snap.state.undos,
-				self.snap.undo_states.len(),
-			);
+	fn new(ttf_context: &'ttf sdl2::ttf::Sdl2TtfContext, options: &Options) -> Game<'ttf> {
+		let mut engine = Engine::new(options.levels(), options.scores_file.to_str().unwrap());
+		engine.debug = options.debug;
+		let config = Config::load(options.config_file.to_str().unwrap());
+		let audio = Audio::new(&options.res_dir, config.volume, config.mute);
+		let skins = skin::list(&options.res_dir);
+		let skin = skins.iter().position(|s| s == &config.skin).unwrap_or(0);
+		let ttf_name = format!("fonts/{}", FONT_FILE);
+		let ttf_file = options.res_dir.join(&ttf_name);
+		let font_size = (TEXT_SIZE * TEXT_RATIO) as u16;
+		let font = match assets::embedded(&ttf_name) {
+			Some(bytes) if !ttf_file.is_file() => RWops::from_bytes(bytes)
+				.and_then(|rwops| ttf_context.load_font_from_rwops(rwops, font_size)),
+			_ => ttf_context.load_font(ttf_file, font_size),
 		}
-	}
+		.expect("Couldn't load the font");
 
-	fn save_state(&self, state: &mut State, full: bool) {
-		*state = State {
-			map: Vec::new(),
-			stored: self.snap.state.stored,
-			px: self.snap.state.px,
-			py: self.snap.state.py,
-			dir: self.snap.state.dir,
-			time_s: self.snap.state.time_s,
-			pushes: self.snap.state.pushes,
-			moves: self.snap.state.moves,
-			undos: self.snap.state.undos,
+		let level = match options.level {
+			Some(level) if level >= engine.levels.len() => {
+				panic!(
+					"No level {}, there are {} levels",
+					level + 1,
+					engine.levels.len()
+				)
+			}
+			Some(level) => level,
+			None => engine.level,
 		};
-		if full {
-			state.map = self.snap.state.map.clone()
-		}
+		let mut g = Game {
+			quit: false,
+			must_draw: true,
+			help: false,
+			stats: false,
+			focused: true,
+			menu: PauseMenu::new(0),
+			config,
+			controllers: Vec::new(),
+			audio,
+			engine,
+			flash: None,
+			drag: None,
+			message: None,
+			anim: None,
+			commands: VecDeque::new(),
+			bw: 0,
+			bh: 0,
+			ox: 0,
+			oy: 0,
+			camera: Default::default(),
+			minimap: true,
+			pixel_ratio: 1.0,
+			tile: (1, 1),
+			skins,
+			skin,
+			width: options.width,
+			height: options.height,
+			font,
+		};
+		g.set_level(level);
+		g
 	}
 
-	fn restore_state(&mut self, state_: State) {
-		let map = self.snap.state.map.clone();
-		self.snap.state = state_;
-		if self.snap.state.map.is_empty() {
-			self.snap.state.map = map;
+	fn load_snapshot(&mut self) {
+		if self.engine.load_snapshot() {
+			self.anim = None;
+			self.commands.clear();
+			self.must_draw = true;
 		}
 	}
 
-	fn save_snapshot(&mut self) {
-		self.snapshots.clear(); // limit snapshots depth to 1
-		let mut snap = Snapshot {
-			undo_states: self.snap.undo_states.clone(),
-			state: Default::default(),
-		};
-		self.save_state(&mut snap.state, true);
-		self.snapshots.push(snap);
-		self.debug_dump();
+	fn switch_worker(&mut self, worker: usize) {
+		if self.engine.switch_worker(worker) {
+			self.must_draw = true;
+		}
 	}
 
-	fn load_snapshot(&mut self) {
-		if let Some(snap) = self.snapshots.pop() {
-			self.snap.undo_states = snap.undo_states;
-			self.restore_state(snap.state);
-			self.save_scores();
-			self.save_snapshot(); // limit snapshots depth to 1
+	fn pop_undo(&mut self) {
+		if self.engine.pop_undo() {
+			self.audio.play(Sound::Undo);
 			self.must_draw = true;
 		}
 	}
 
-	fn save_score(&mut self) {
-		let mut push_score = true;
-		for score in &self.scores {
-			if score.level as usize == self.level {
-				push_score = false;
-			}
+	fn pop_redo(&mut self) {
+		if self.engine.pop_redo() {
+			self.must_draw = true;
 		}
-		if push_score {
-			self.scores.push(Score {
-				level: self.level as u16,
-				pushes: self.snap.state.pushes as u16,
-				moves: self.snap.state.moves as u16,
-				time_s: self.snap.state.time_s,
-			});
+	}
+
+	fn set_level(&mut self, level: usize) -> bool {
+		if self.engine.set_level(level) {
+			self.level_started();
+			true
+		} else {
+			false
 		}
 	}
 
-	fn save_scores(&self) {
-		if !self.scores.is_empty() {
-			let mut file = File::create(&self.scores_file).unwrap();
-			writeln!(file, "{}", VERSION).unwrap();
-			writeln!(file, "{}", self.scores.len()).unwrap();
-			for s in &self.scores {
-				writeln!(file, "{} {} {} {}", s.level, s.pushes, s.moves, s.time_s).unwrap();
-			}
+	/// Switch between the normal and pull modes, restarting the level
+	fn toggle_reverse(&mut self) {
+		self.engine.set_reverse(!self.engine.reverse);
+		self.level_started();
+		let mode = if self.engine.reverse {
+			"Pull mode: bring the crates back"
+		} else {
+			"Normal mode"
+		};
+		self.message = Some((mode.to_string(), Instant::now()));
+	}
+
+	fn level_started(&mut self) {
+		self.must_draw = true;
+		self.drag = None;
+		self.message = None;
+		self.anim = None;
+		self.commands.clear();
+		self.camera.zoom = 1.0;
+		self.layout();
+		self.update_camera(true);
+	}
+
+	/// Pause the game, hiding the level behind the pause menu
+	/// Drop the queued moves, the running animation and the drag: no moves behind the pause
+	/// menu, the help or the statistics, while the clock is stopped
+	fn stop_play_input(&mut self) {
+		self.commands.clear();
+		self.anim = None;
+		self.drag = None;
+		self.must_draw = true;
+	}
+
+	fn pause(&mut self) {
+		if self.engine.playing() {
+			self.engine.status = Status::Pause;
+			self.menu = PauseMenu::new(self.engine.level);
+			self.stop_play_input();
 		}
 	}
 
-	fn load_scores(scores_file: &str) -> Vec<Score> {
-		macro_rules! scan {
-			($string:expr, $sep:expr, $( $x:ty ),+ ) => {{
-				let mut iter = $string.split($sep);
-				($(iter.next().and_then(|word| word.parse::<$x>().ok()).unwrap(),)*)
-			}}
+	fn resume(&mut self) {
+		self.engine.resume();
+		self.must_draw = true;
+	}
+
+	/// Whether the window is wide enough to show the HUD on a side panel, rather than on
+	/// a bottom bar
+	fn side_panel(&self) -> bool {
+		self.width as f32 >= self.height as f32 * PANEL_RATIO
+	}
+
+	fn map_width(&self) -> usize {
+		if self.side_panel() {
+			self.width.saturating_sub(PANEL_WIDTH)
+		} else {
+			self.width
 		}
-		let mut version = 0;
-		let mut nscores = 0;
-		let mut ret = Vec::new();
-		if let Ok(file) = File::open(scores_file) {
-			let mut reader = BufReader::new(file);
-			let mut line = String::new();
-			if let Ok(_nbytes) = reader.read_line(&mut line) {
-				version = scan!(line, char::is_whitespace, u8).0;
-			}
-			if version != VERSION {
-				panic!(
-					"Invalid scores version. Please delete the scores file {}.",
-					&scores_file
-				);
-			}
-			line.clear();
-			if let Ok(_nbytes) = reader.read_line(&mut line) {
-				nscores = scan!(line, char::is_whitespace, u8).0;
-			}
-			let mut sscores = String::new();
-			reader.read_to_string(&mut sscores).unwrap();
-			for line in sscores.lines() {
-				let (level, pushes, moves, time_s) =
-					scan!(line, char::is_whitespace, u16, u16, u16, u32);
-				ret.push(Score {
-					level,
-					pushes,
-					moves,
-					time_s,
-				});
-			}
-			if nscores as usize != ret.len() {
-				panic!(
-					"Invalid number of scores (read {} parsed {}). Please delete the scores file {}.",
-					nscores, ret.len(), &scores_file
-				);
-			}
+	}
+
+	fn map_height(&self) -> usize {
+		if self.side_panel() {
+			self.height
+		} else {
+			self.height.saturating_sub(self.font.height() as usize)
 		}
-		ret
 	}
 
-	fn pop_undo(&mut self) {
-		if let Some(state) = self.snap.undo_states.pop() {
-			self.restore_state(state);
-			self.save_scores();
-			self.snap.state.undos += 1;
-			self.debug_dump();
+	fn topology(&self) -> Topology {
+		self.engine.levels[self.engine.level].topology
+	}
+
+	/// Size of the current level, in pixels
+	fn level_size(&self) -> (f32, f32) {
+		let level = &self.engine.levels[self.engine.level];
+		let (w, h) = level.topology.extent(level.w, level.h);
+		(w * self.bw as f32, h * self.bh as f32)
+	}
+
+	/// Fit the blocks of the current level in the window, keeping the sprites aspect ratio,
+	/// then apply the camera zoom and center the map if it fits
+	fn layout(&mut self) {
+		let level = &self.engine.levels[self.engine.level];
+		let (w, h) = level.topology.extent(level.w, level.h);
+		let map_height = self.map_height();
+		let (tw, th) = self.tile;
+		let fit = (self.map_width() as f32 / w).min(map_height as f32 * tw as f32 / th as f32 / h);
+		let base = std::cmp::max(MIN_BW, fit as usize);
+		let bw = (base as f32 * self.camera.zoom) as usize;
+		self.bw = bw.max(MIN_BW).min(MAX_BW.max(base));
+		self.bh = self.bw * th / tw;
+		self.camera.zoom = self.bw as f32 / base as f32;
+		let (width, height) = self.level_size();
+		self.ox = self.map_width().saturating_sub(width as usize) / 2;
+		self.oy = map_height.saturating_sub(height as usize) / 2;
+		self.must_draw = true;
+	}
+
+	/// Follow the tiles size of the current skin, to keep their aspect ratio
+	fn set_tile_size(&mut self, (w, h): (u32, u32)) {
+		self.tile = (w.max(1) as usize, h.max(1) as usize);
+		self.layout();
+		self.update_camera(true);
+	}
+
+	/// Scroll the camera to keep the player in view
+	fn update_camera(&mut self, snap: bool) {
+		let (x, y) = self.cell_pos(self.engine.snap.state.px, self.engine.snap.state.py);
+		let target = (
+			(x as usize + self.bw / 2) as f32,
+			(y as usize + self.bh / 2) as f32,
+		);
+		let view = (self.map_width() as f32, self.map_height() as f32);
+		let size = self.level_size();
+		if self.camera.follow(target, view, size, snap) {
 			self.must_draw = true;
 		}
 	}
 
-	fn push_undo(&mut self, full: bool) {
-		let mut s = Default::default();
-		self.save_state(&mut s, full);
-		self.snap.undo_states.push(s);
+	/// Position of the box of the cell i:j from the map top left corner, in pixels
+	fn cell_pos(&self, i: usize, j: usize) -> (i32, i32) {
+		let (x, y) = self.topology().origin(i, j);
+		((x * self.bw as f32) as i32, (y * self.bh as f32) as i32)
 	}
 
-	fn load_levels(levels_file: &str) -> Vec<Level> {
-		let mut levels = Vec::new();
-		let mut vlevels = Vec::new();
-		let mut slevel = String::new();
-		let mut level = 1;
-		let mut slevels = String::new();
-		let mut f = File::open(levels_file)
-			.unwrap_or_else(|_| panic!("Couldn't open the levels {}", levels_file));
-		f.read_to_string(&mut slevels).unwrap();
-		for line in slevels.lines() {
-			if line.is_empty() {
-				if !slevel.is_empty() {
-					vlevels.push(slevel);
-					slevel = "".to_string();
-				}
-				continue;
-			}
-			if line.starts_with(';') {
-				continue;
-			}
-			slevel = format!("{}\n{}", slevel, line);
+	/// Corners of the cell i:j with its box at x:y, shrunk around its center by `scale`
+	fn corners(&self, i: usize, j: usize, (x, y): (i32, i32), scale: f32) -> Vec<Point> {
+		let outline = self.topology().outline(i, j);
+		let n = outline.len() as f32;
+		let cx = outline.iter().map(|c| c.0).sum::<f32>() / n;
+		let cy = outline.iter().map(|c| c.1).sum::<f32>() / n;
+		outline
+			.iter()
+			.map(|&(u, v)| {
+				let u = cx + (u - cx) * scale;
+				let v = cy + (v - cy) * scale;
+				Point::new(
+					x + (u * self.bw as f32) as i32,
+					y + (v * self.bh as f32) as i32,
+				)
+			})
+			.collect()
+	}
+
+	/// Window position of the map top left corner
+	fn origin(&self) -> (i32, i32) {
+		(
+			self.ox as i32 - self.camera.x.round() as i32,
+			self.oy as i32 - self.camera.y.round() as i32,
+		)
+	}
+
+	fn zoom(&mut self, zoom_in: bool) {
+		if zoom_in {
+			self.camera.zoom_in();
+		} else {
+			self.camera.zoom_out();
+		}
+		self.layout();
+		self.update_camera(true);
+	}
+
+	/// Follow the window size, in pixels
+	fn resize(&mut self, canvas: &Canvas<Window>) {
+		let (width, height) = canvas.output_size().expect("Couldn't get output size");
+		let (window_width, _) = canvas.window().size();
+		self.width = width as usize;
+		self.height = height as usize;
+		self.pixel_ratio = width as f32 / window_width.max(1) as f32;
+		self.layout();
+		self.update_camera(true);
+	}
+
+	fn toggle_fullscreen(&mut self, canvas: &mut Canvas<Window>) {
+		let window = canvas.window_mut();
+		let fullscreen = match window.fullscreen_state() {
+			FullscreenType::Off => FullscreenType::Desktop,
+			_ => FullscreenType::Off,
+		};
+		if let Err(e) = window.set_fullscreen(fullscreen) {
+			println!("Couldn't toggle fullscreen: {}", e);
 		}
-		if !slevel.is_empty() {
-			vlevels.push(slevel);
+		self.resize(canvas);
+	}
+
+	fn try_move(&mut self, dx: isize, dy: isize) {
+		if let Some(moved) = self.engine.try_move(dx, dy) {
+			self.audio.play(match moved {
+				Moved::Step => Sound::Step,
+				Moved::Push => Sound::Push,
+				Moved::Store => Sound::Store,
+				Moved::Win => Sound::Win,
+			});
+			self.must_draw = true;
 		}
-		for s in vlevels {
-			let mut lev = Level {
-				..Default::default()
+	}
+
+	/// Convert window coordinates to map cell coordinates
+	fn cell_at(&self, x: i32, y: i32) -> Option<(usize, usize)> {
+		let (ox, oy) = self.origin();
+		let x = (x as f32 * self.pixel_ratio) as i32 - ox;
+		let y = (y as f32 * self.pixel_ratio) as i32 - oy;
+		if x < 0 || y < 0 || x + ox >= self.map_width() as i32 || y + oy >= self.map_height() as i32
+		{
+			return None;
+		}
+		let level = &self.engine.levels[self.engine.level];
+		let (x, y) = (x as f32 / self.bw as f32, y as f32 / self.bh as f32);
+		level.topology.cell_at(level.w, level.h, x, y)
+	}
+
+	/// Run a command now, or queue it after the running animation
+	fn command(&mut self, command: Command) {
+		self.commands.push_back(command);
+		self.run_commands();
+	}
+
+	/// Run queued commands, until one of them starts an animation
+	fn run_commands(&mut self) {
+		while self.anim.is_none() {
+			let command = match self.commands.pop_front() {
+				Some(command) => command,
+				None => break,
 			};
-			let mut stores = 0;
-			let mut player_found = false;
-			for line in s.lines() {
-				if line.len() > lev.w {
-					lev.w = line.len();
-				}
+			// won or paused, the rest of the moves are dropped
+			if !self.engine.playing() {
+				self.commands.clear();
+				break;
 			}
-			for line in s.lines() {
-				if line.is_empty() {
-					continue;
-				}
-				let mut v = vec![EMPTY; lev.w];
-				for (i, e) in line.chars().enumerate() {
-					match e {
-						C_EMPTY => {
-							v[i] = EMPTY;
-						}
-						C_STORE => {
-							v[i] = STORE;
-							stores += 1;
-						}
-						C_CRATE => {
-							v[i] = CRATE;
-							lev.crates += 1;
-						}
-						C_STORED => {
-							v[i] = CRATE | STORE;
-							stores += 1;
-							lev.crates += 1;
-							lev.stored += 1;
-						}
-						C_PLAYER => {
-							if player_found {
-								panic!("Player found multiple times in level {}", level);
-							};
-							lev.px = i;
-							lev.py = lev.h;
-							player_found = true;
-							v[i] = EMPTY;
-						}
-						C_SPLAYER => {
-							if player_found {
-								panic!("Player found multiple times in level {}", level);
-							};
-							lev.px = i;
-							lev.py = lev.h;
-							player_found = true;
-							v[i] = STORE;
-							stores += 1;
-						}
-						C_WALL => {
-							v[i] = WALL;
-						}
-						_ => {
-							panic!("Invalid element [{}] in level", e);
-						}
+			let before = (
+				self.engine.snap.state.px,
+				self.engine.snap.state.py,
+				self.engine.snap.state.pushes,
+			);
+			let worker = self.engine.snap.state.worker;
+			let moves = matches!(command, Command::Move(..) | Command::Undo | Command::Redo);
+			match command {
+				Command::Move(dx, dy) => self.try_move(dx, dy),
+				Command::Worker => {
+					if self.engine.next_worker() {
+						self.must_draw = true;
 					}
 				}
-				lev.map.push(v);
-				lev.h += 1;
-			}
-			if lev.crates != stores {
-				panic!(
-					"Mismatch between crates={} and stores={} in level",
-					lev.crates, stores
-				);
+				Command::Switch(worker) => self.switch_worker(worker),
+				Command::Undo => self.pop_undo(),
+				Command::Redo => self.pop_redo(),
+				Command::Save => self.engine.save_snapshot(),
 			}
-			if !player_found {
-				panic!("Player not found in level {}", level);
-			} else {
+			// an undo or redo across a worker switch jumps to the other worker
+			if moves && self.engine.snap.state.worker == worker {
+				self.animate(before);
 			}
-			levels.push(lev);
-			level += 1;
-		}
-		levels
-	}
-
-	fn new(
-		ttf_context: &'ttf sdl2::ttf::Sdl2TtfContext,
-		root_dir: &std::path::Path,
-		width: usize,
-		height: usize,
-	) -> Game<'ttf> {
-		let levels_file = root_dir.join("res").join("levels").join(LEVELS_FILE);
-		let levels_file = levels_file.to_str().unwrap();
-		let scores_file = root_dir.join(SCORES_FILE).to_str().unwrap().to_string();
-		let levels = Game::load_levels(levels_file);
-		let scores = Game::load_scores(&scores_file);
-		let ttf_file = root_dir.join("res").join("fonts").join(FONT_FILE);
-		let font = ttf_context
-			.load_font(ttf_file, (TEXT_SIZE * TEXT_RATIO) as u16)
-			.expect("Couldn't load the font");
-
-		let mut level = 0;
-		let mut dones = vec![false; levels.len()];
-		for score in &scores {
-			if (score.level as usize) < levels.len() {
-				dones[score.level as usize] = true;
-			}
-		}
-		for done in dones {
-			if !done {
-				break;
-			}
-			level += 1;
 		}
+	}
 
-		let mut g = Game {
-			quit: false,
-			status: Status::Play,
-			must_draw: true,
-			debug: false,
-			levels,
-			snapshots: Vec::new(),
-			snap: Default::default(),
-			level,
-			last_ticks: SystemTime::now(),
-			scores,
-			scores_file,
-			bw: 0,
-			bh: 0,
-			width,
-			height,
-			font,
+	/// Animate the player (and the crate it pushed or pulled back) from its position `before`
+	fn animate(&mut self, before: (usize, usize, i32)) {
+		let (x0, y0, pushes) = before;
+		let (x1, y1) = (self.engine.snap.state.px, self.engine.snap.state.py);
+		let dx = x1 as isize - x0 as isize;
+		let dy = y1 as isize - y0 as isize;
+		// slides are only drawn on square grids, where the moves keep their direction, and
+		// not out of teleporters
+		let level = &self.engine.levels[self.engine.level];
+		if self.config.anim_ms == 0
+			|| dx.abs() + dy.abs() != 1
+			|| self.topology() != Topology::Square
+			|| matches!(level.tiles[y1][x1], Tile::Teleporter(_))
+		{
+			return;
+		}
+		let offset = |(x, y): (usize, usize), n: isize| {
+			(
+				(x as isize + n * dx) as usize,
+				(y as isize + n * dy) as usize,
+			)
 		};
-		g.set_level(level);
-		g
+		let state = &self.engine.snap.state;
+		// the crate goes ahead of the player after a push or the undo of a pull, and
+		// follows it after a pull or the undo of a push
+		let crate_ = if state.pushes == pushes {
+			None
+		} else if (state.pushes > pushes) != self.engine.reverse {
+			// a crate sliding over ice or teleported just shows at its rest
+			let (cx, cy) = offset((x1, y1), 1);
+			Some(((x1, y1), (cx, cy))).filter(|_| state.map[cy][cx] & CRATE == CRATE)
+		} else {
+			Some((offset((x0, y0), -1), (x0, y0)))
+		};
+		self.anim = Some(Anim {
+			player: ((x0, y0), (x1, y1)),
+			crate_,
+			start: Instant::now(),
+		});
 	}
 
-	fn set_level(&mut self, level: usize) -> bool {
-		if level < self.levels.len() {
-			self.status = Status::Play;
-			self.must_draw = true;
-			self.level = level;
-			self.snap.state.moves = 0;
-			self.snap.state.pushes = 0;
-			self.snap.state.time_s = 0;
-			self.snap.state.map = self.levels[level].map.clone();
-			self.snap.undo_states = Vec::new();
-			self.levels[self.level].crates = self.levels[self.level].crates;
-			self.snap.state.stored = self.levels[level].stored;
-			self.levels[self.level].w = self.levels[level].w;
-			self.levels[self.level].h = self.levels[level].h;
-			self.snap.state.px = self.levels[level].px;
-			self.snap.state.py = self.levels[level].py;
-			self.bw = self.width / self.levels[self.level].w;
-			self.bh = (self.height - TEXT_SIZE * TEXT_RATIO) / self.levels[self.level].h;
-			true
-		} else {
-			false
-		}
+	/// Progress of the running animation, from 0 to 1
+	fn anim_progress(&self) -> Option<f32> {
+		self.anim.as_ref().map(|anim| {
+			let elapsed = anim.start.elapsed().as_millis();
+			(elapsed as f32 / self.config.anim_ms as f32).min(1.0)
+		})
 	}
 
-	fn can_move(&self, x: usize, y: usize) -> bool {
-		if x < self.levels[self.level].w && y < self.levels[self.level].h {
-			let e = self.snap.state.map[y][x];
-			if e == EMPTY || e == STORE {
-				return true;
+	/// Walk the player to x:y along the shortest path, one move at a time, instead of the
+	/// moves still queued: the path starts where the running animation ends, and in pull
+	/// mode it pulls no crate along
+	fn walk_to(&mut self, x: usize, y: usize) {
+		self.commands.clear();
+		let from = (self.engine.snap.state.px, self.engine.snap.state.py);
+		let find = if self.engine.reverse {
+			path::find_pull_free_path
+		} else {
+			path::find_path
+		};
+		if let Some(moves) = find(self.topology(), &self.engine.walk_map(), from, (x, y)) {
+			for (dx, dy) in moves {
+				self.command(Command::Move(dx, dy));
 			}
+		} else {
+			self.flash = Some((x, y, Instant::now()));
+			self.must_draw = true;
 		}
-		false
 	}
 
-	/// Try to move to x+dx:y+dy and also push to x+2dx:y+2dy
-	fn try_move(&mut self, dx: isize, dy: isize) {
-		let mut do_it = false;
-		let x = self.snap.state.px as isize + dx;
-		let y = self.snap.state.py as isize + dy;
-		if x < 0 || y < 0 {
-			return;
-		}
-		let x = x as usize;
-		let y = y as usize;
-		if x >= self.levels[self.level].w || y >= self.levels[self.level].h {
-			return;
-		}
-		if self.snap.state.map[y][x] & CRATE == CRATE {
-			let to_x = (x as isize + dx) as usize;
-			let to_y = (y as isize + dy) as usize;
-			if self.can_move(to_x, to_y) {
-				do_it = true;
-				self.push_undo(true);
-				self.snap.state.pushes += 1;
-				self.snap.state.map[y][x] &= !CRATE;
-				if self.snap.state.map[y][x] & STORE == STORE {
-					self.snap.state.stored -= 1;
-				}
-				self.snap.state.map[to_y][to_x] |= CRATE;
-				if self.snap.state.map[to_y][to_x] & STORE == STORE {
-					self.snap.state.stored += 1;
-					if self.snap.state.stored == self.levels[self.level].crates {
-						self.status = Status::Win;
-					}
-				}
+	/// Push the crate at `from` to `to`, walking around other crates, one move at a time,
+	/// instead of the moves still queued
+	fn push_to(&mut self, from: (usize, usize), to: (usize, usize)) {
+		self.commands.clear();
+		let player = (self.engine.snap.state.px, self.engine.snap.state.py);
+		let map = self.engine.walk_map();
+		if let Some(moves) = path::find_push_path(self.topology(), &map, player, from, to) {
+			for (dx, dy) in moves {
+				self.command(Command::Move(dx, dy));
 			}
 		} else {
-			do_it = self.can_move(x, y);
-			if do_it {
-				self.push_undo(false);
-			}
-		}
-		if do_it {
-			self.snap.state.moves += 1;
-			self.snap.state.px = x;
-			self.snap.state.py = y;
-			self.snap.state.dir = 0;
-			match dx {
-				-1 => {
-					self.snap.state.dir = 0;
-				}
-				1 => {
-					self.snap.state.dir = 2;
-				}
-				_ => {}
-			}
-			match dy {
-				-1 => {
-					self.snap.state.dir = 1;
-				}
-				1 => {
-					self.snap.state.dir = 3;
-				}
-				_ => {}
-			}
-			if let Status::Win = self.status {
-				self.save_score();
-				self.save_scores();
-			}
-			self.debug_dump();
+			self.flash = Some((to.0, to.1, Instant::now()));
+			self.message = Some(("Can't push the crate there".to_string(), Instant::now()));
 			self.must_draw = true;
 		}
 	}
@@ -554,151 +606,700 @@ impl<'ttf> Game<'ttf> {
 	fn draw_map(
 		&mut self,
 		canvas: &mut sdl2::render::Canvas<sdl2::video::Window>,
-		textures: &[sdl2::render::Texture<'_>],
+		skin: &mut Skin,
 		texture_creator: &sdl2::render::TextureCreator<sdl2::video::WindowContext>,
 	) {
-		let curr_ticks = SystemTime::now();
-		let duration = curr_ticks
-			.duration_since(self.last_ticks)
-			.unwrap()
-			.as_millis();
-		if duration > 1000 {
-			if let Status::Play = self.status {
-				self.snap.state.time_s += (duration / 1000) as u32;
-			}
-			self.last_ticks = curr_ticks;
+		if self.engine.tick(self.focused && !self.help && !self.stats) {
+			self.must_draw = true;
+		}
+		if let Some((_, _, t)) = self.flash {
+			if t.elapsed().as_millis() > FLASH_MS {
+				self.flash = None;
+				self.must_draw = true;
+			}
+		}
+		if let Some((_, t)) = &self.message {
+			if t.elapsed().as_millis() > MESSAGE_MS {
+				self.message = None;
+				self.must_draw = true;
+			}
+		}
+		if let Some(t) = self.anim_progress() {
 			self.must_draw = true;
+			if t >= 1.0 {
+				self.anim = None;
+				self.run_commands();
+			}
 		}
+		self.update_camera(false);
 		if self.must_draw {
 			canvas.set_draw_color(Color::RGB(0, 0, 0));
 			canvas.clear();
-			// bottom status bar
-			canvas
-				.copy(
-					&textures[N_WHITE],
-					None,
-					Rect::new(
-						0,
-						(self.height - TEXT_SIZE * TEXT_RATIO) as i32,
-						self.width as u32,
-						(TEXT_SIZE * TEXT_RATIO) as u32,
-					),
-				)
-				.expect("Couldn't copy texture into window");
-			let x = (WIDTH - self.levels[self.level].w * self.bw) / 2;
-			let y = 0;
-			for (j, line) in self.snap.state.map.iter().enumerate() {
+			let (x, y) = self.origin();
+			let (bw, bh) = (self.bw as i32, self.bh as i32);
+			let square = self.topology() == Topology::Square;
+			canvas.set_clip_rect(Rect::new(
+				0,
+				0,
+				self.map_width() as u32,
+				self.map_height() as u32,
+			));
+			let progress = self.anim_progress();
+			let moving_crate = self.anim.as_ref().and_then(|anim| anim.crate_.map(|c| c.1));
+			for (j, line) in self.engine.snap.state.map.iter().enumerate() {
 				for (i, &e) in line.iter().enumerate() {
+					let (cx, cy) = self.cell_pos(i, j);
+					let (cx, cy) = (x + cx, y + cy);
+					if cy + bh < 0 || cy >= self.map_height() as i32 {
+						continue;
+					}
+					if cx + bw < 0 || cx >= self.map_width() as i32 {
+						continue;
+					}
+					let e = match self.drag {
+						Some((di, dj, _, _)) if di == i && dj == j => e & !CRATE,
+						_ if moving_crate == Some((i, j)) => e & !CRATE,
+						_ => e,
+					};
+					let e = self.drawn(i, j, e);
+					let rect = Rect::new(cx, cy, self.bw as u32, self.bh as u32);
+					if e == WALL && !square {
+						self.draw_shape(canvas, i, j, N_WALL, (cx, cy));
+						continue;
+					}
+					if e == WALL {
+						skin.draw_wall(canvas, self.wall_mask(i, j), rect);
+						continue;
+					}
+					if !self.engine.levels[self.engine.level].inside[j][i] {
+						continue;
+					}
+					let player = progress.is_none()
+						&& self.engine.snap.state.px == i
+						&& self.engine.snap.state.py == j;
 					let idx = if e == EMPTY {
-						if self.snap.state.px == i && self.snap.state.py == j {
-							match self.snap.state.dir {
-								0 => N_PLAYERW,
-								1 => N_PLAYERN,
-								2 => N_PLAYERE,
-								3 => N_PLAYERS,
-								_ => N_PLAYERW,
-							}
+						if player {
+							self.player_sprite(false)
 						} else {
 							N_EMPTY
 						}
 					} else if e == STORE {
-						if self.snap.state.px == i && self.snap.state.py == j {
-							match self.snap.state.dir {
-								0 => N_SPLAYERW,
-								1 => N_SPLAYERN,
-								2 => N_SPLAYERE,
-								3 => N_SPLAYERS,
-								_ => N_SPLAYERW,
-							}
+						if player {
+							self.player_sprite(true)
 						} else {
 							N_STORE
 						}
 					} else if e == CRATE {
 						N_CRATE
-					} else if e == WALL {
-						N_WALL
-					} else if e == CRATE | STORE {
+					} else if e == CRATE | STORE && self.engine.is_stored(i, j) {
 						N_STORED
+					} else if e == CRATE | STORE {
+						// on a goal of another color
+						N_CRATE
 					} else {
 						N_EMPTY
 					};
-					canvas
-						.copy(
-							&textures[idx as usize],
-							None,
-							Rect::new(
-								(x + i * self.bw) as i32,
-								(y + j * self.bh) as i32,
-								self.bw as u32,
-								self.bh as u32,
-							),
-						)
-						.expect("Couldn't copy texture into window");
-				}
-			}
-			let state = match self.status {
+					// the other workers face the player
+					let idle = !player && self.engine.snap.state.workers.contains(&(i, j));
+					let idx = match idx {
+						N_EMPTY if idle => N_PLAYERS,
+						N_STORE if idle => N_SPLAYERS,
+						_ => idx,
+					};
+					if square {
+						skin.draw_tinted(canvas, idx, rect, self.tint(i, j, idx));
+					} else {
+						self.draw_shape(canvas, i, j, idx, (cx, cy));
+					}
+					if idx == N_EMPTY {
+						self.draw_tile(canvas, i, j, (cx, cy));
+					}
+				}
+			}
+			if let (Some(anim), Some(t)) = (&self.anim, progress) {
+				let lerp = |(from, to): ((usize, usize), (usize, usize))| {
+					let i = from.0 as f32 + (to.0 as f32 - from.0 as f32) * t;
+					let j = from.1 as f32 + (to.1 as f32 - from.1 as f32) * t;
+					Rect::new(
+						x + (i * bw as f32) as i32,
+						y + (j * bh as f32) as i32,
+						self.bw as u32,
+						self.bh as u32,
+					)
+				};
+				if let Some(c) = anim.crate_ {
+					let (i, j) = c.1;
+					skin.draw_tinted(canvas, N_CRATE, lerp(c), self.tint(i, j, N_CRATE));
+				}
+				let (i, j) = anim.player.1;
+				let idx = self.player_sprite(self.engine.is_goal(i, j));
+				skin.draw(canvas, idx, lerp(anim.player));
+			}
+			// highlight the active worker among several
+			if progress.is_none() && self.engine.snap.state.workers.len() > 1 {
+				let (px, py) = (self.engine.snap.state.px, self.engine.snap.state.py);
+				self.frame_cell(canvas, px, py, Color::RGB(255, 255, 255));
+			}
+			if let Some((i, j, _)) = self.flash {
+				self.frame_cell(canvas, i, j, Color::RGB(255, 0, 0));
+			}
+			if let Some((i, j, mx, my)) = self.drag {
+				let rect = Rect::new(
+					(mx as f32 * self.pixel_ratio) as i32 - self.bw as i32 / 2,
+					(my as f32 * self.pixel_ratio) as i32 - self.bh as i32 / 2,
+					self.bw as u32,
+					self.bh as u32,
+				);
+				let tint = self.tint(i, j, N_CRATE);
+				if square {
+					skin.draw_tinted(canvas, N_CRATE, rect, tint);
+				} else {
+					let corners = self.corners(i, j, (rect.x(), rect.y()), CRATE_SCALE);
+					canvas.set_draw_color(tint.unwrap_or(Color::RGB(192, 128, 0)));
+					fill_polygon(canvas, &corners);
+				}
+			}
+			if self.minimap {
+				self.draw_minimap(canvas);
+			}
+			canvas.set_clip_rect(None);
+			if let Status::Pause = self.engine.status {
+				// hide the level so that the clock can't be cheated
+				let fullscreen = canvas.window().fullscreen_state() != FullscreenType::Off;
+				let lines = self.pause_lines(fullscreen);
+				self.draw_overlay(canvas, texture_creator, &lines, 255);
+			} else if self.help {
+				self.draw_overlay(canvas, texture_creator, &self.config.help(), 192);
+			} else if self.stats {
+				let lines = stats::summary(&self.engine.history, self.engine.levels.len());
+				self.draw_overlay(canvas, texture_creator, &lines, 192);
+			}
+			let state = match self.engine.status {
 				Status::Win => "You win! Press Return..",
 				Status::Pause => "*PAUSE* Press Space..",
+				Status::Pull => "PULL",
 				_ => "",
 			};
-			let ts = self.snap.state.time_s % 60;
-			let tm = (self.snap.state.time_s / 60) % 60;
-			let th = self.snap.state.time_s / 3600;
-			let text = format!(
-				"{:02}| moves: {:04} pushes: {:04} time:{}:{:02}:{:02} {}",
-				self.level + 1,
-				self.snap.state.moves,
-				self.snap.state.pushes,
-				th,
-				tm,
-				ts,
-				state
-			);
-			let texture = create_texture_from_text(texture_creator, &self.font, &text, 0, 0, 0)
+			let state = match &self.message {
+				Some((message, _)) => format!("{} {}", state, message),
+				None => state.to_string(),
+			};
+			let items = self.hud_items(state.trim());
+			self.draw_hud(canvas, texture_creator, &items);
+			canvas.present();
+			self.must_draw = false;
+		}
+	}
+
+	/// Draw the cell i:j, with its box at x:y, as the element of sprite `idx` on grids
+	/// the skins have no tiles for: walls, floors darker on the goals, crates and the
+	/// player shrunk inside
+	fn draw_shape(
+		&self,
+		canvas: &mut Canvas<Window>,
+		i: usize,
+		j: usize,
+		idx: usize,
+		at: (i32, i32),
+	) {
+		let goal = [
+			N_STORE, N_STORED, N_SPLAYERW, N_SPLAYERN, N_SPLAYERE, N_SPLAYERS,
+		]
+		.contains(&idx);
+		let floor = match (idx, self.tint(i, j, N_STORE)) {
+			(N_WALL, _) => Color::RGB(160, 160, 160),
+			(_, Some(tint)) if goal => Color::RGB(tint.r / 2, tint.g / 2, tint.b / 2),
+			_ if goal => Color::RGB(96, 96, 0),
+			_ => Color::RGB(48, 48, 48),
+		};
+		let tint = self.tint(i, j, idx);
+		let corners = self.corners(i, j, at, 1.0);
+		canvas.set_draw_color(floor);
+		fill_polygon(canvas, &corners);
+		let item = match idx {
+			N_WALL | N_EMPTY | N_STORE => None,
+			N_CRATE => Some((tint.unwrap_or(Color::RGB(192, 128, 0)), CRATE_SCALE)),
+			N_STORED => Some((tint.unwrap_or(Color::RGB(0, 192, 0)), CRATE_SCALE)),
+			_ => Some((Color::RGB(0, 128, 255), PLAYER_SCALE)),
+		};
+		if let Some((color, scale)) = item {
+			canvas.set_draw_color(color);
+			fill_polygon(canvas, &self.corners(i, j, at, scale));
+		}
+		let mut edges = corners;
+		edges.push(edges[0]);
+		canvas.set_draw_color(Color::RGB(0, 0, 0));
+		canvas
+			.draw_lines(edges.as_slice())
+			.expect("Couldn't draw lines");
+	}
+
+	/// Draw the special tile of the cell i:j, with its box at x:y, over its floor: ice
+	/// tinted, an arrow the way into one-way floors, teleporters framed by pair
+	fn draw_tile(&self, canvas: &mut Canvas<Window>, i: usize, j: usize, at: (i32, i32)) {
+		match self.engine.levels[self.engine.level].tiles[j][i] {
+			Tile::Floor => {}
+			Tile::Ice => {
+				canvas.set_blend_mode(BlendMode::Blend);
+				canvas.set_draw_color(Color::RGBA(160, 224, 255, 128));
+				fill_polygon(canvas, &self.corners(i, j, at, 1.0));
+				canvas.set_blend_mode(BlendMode::None);
+			}
+			Tile::OneWay(dx, dy) => {
+				let center = self.corners(i, j, at, 0.0)[0];
+				let r = self.bw.min(self.bh) as f32 * 0.3;
+				let len = ((dx * dx + dy * dy) as f32).sqrt();
+				let (ux, uy) = (dx as f32 / len, dy as f32 / len);
+				let point = |along: f32, across: f32| {
+					Point::new(
+						center.x() + (r * (along * ux - across * uy)) as i32,
+						center.y() + (r * (along * uy + across * ux)) as i32,
+					)
+				};
+				canvas.set_draw_color(Color::RGB(224, 224, 224));
+				fill_polygon(
+					canvas,
+					&[point(1.0, 0.0), point(-0.5, 0.8), point(-0.5, -0.8)],
+				);
+			}
+			Tile::Teleporter(n) => {
+				let (r, g, b) = TELEPORTER_COLORS[n as usize % TELEPORTER_COLORS.len()];
+				let mut corners = self.corners(i, j, at, 0.8);
+				corners.push(corners[0]);
+				canvas.set_draw_color(Color::RGB(r, g, b));
+				canvas
+					.draw_lines(corners.as_slice())
+					.expect("Couldn't draw lines");
+			}
+		}
+	}
+
+	/// Tint of the sprite `idx` drawn at i:j: the color of a colored crate, or of a
+	/// colored goal
+	fn tint(&self, i: usize, j: usize, idx: usize) -> Option<Color> {
+		let color = match idx {
+			N_CRATE | N_STORED => self.engine.snap.state.colors[j][i],
+			N_STORE => self.engine.goal_color(i, j),
+			_ => 0,
+		};
+		match color {
+			0 => None,
+			_ => {
+				let (r, g, b) = TINTS[color as usize - 1];
+				Some(Color::RGB(r, g, b))
+			}
+		}
+	}
+
+	/// Draw a frame around the cell i:j
+	fn frame_cell(&self, canvas: &mut Canvas<Window>, i: usize, j: usize, color: Color) {
+		let (x, y) = self.origin();
+		let (cx, cy) = self.cell_pos(i, j);
+		canvas.set_draw_color(color);
+		if self.topology() != Topology::Square {
+			let mut corners = self.corners(i, j, (x + cx, y + cy), 1.0);
+			corners.push(corners[0]);
+			canvas
+				.draw_lines(corners.as_slice())
+				.expect("Couldn't draw lines");
+			return;
+		}
+		for k in 0..ZOOM {
+			canvas
+				.draw_rect(Rect::new(
+					x + cx + k as i32,
+					y + cy + k as i32,
+					(self.bw - 2 * k) as u32,
+					(self.bh - 2 * k) as u32,
+				))
+				.expect("Couldn't draw rect");
+		}
+	}
+
+	fn show_volume(&mut self) {
+		let text = format!("Volume {}%", self.audio.volume());
+		self.message = Some((text, Instant::now()));
+		self.must_draw = true;
+	}
+
+	/// Map element `e` at i:j as drawn, its STORE bit marking the goals: the crates start
+	/// cells when pulling
+	fn drawn(&self, i: usize, j: usize, e: u8) -> u8 {
+		if e == WALL || !self.engine.reverse {
+			e
+		} else if self.engine.is_goal(i, j) {
+			e | STORE
+		} else {
+			e & !STORE
+		}
+	}
+
+	/// Which of the 4 neighbors of the wall at x:y are walls too
+	fn wall_mask(&self, x: usize, y: usize) -> usize {
+		let map = &self.engine.snap.state.map;
+		let wall = |x: usize, y: usize| map[y][x] == WALL;
+		let mut mask = 0;
+		if y > 0 && wall(x, y - 1) {
+			mask |= WALL_N;
+		}
+		if x + 1 < map[y].len() && wall(x + 1, y) {
+			mask |= WALL_E;
+		}
+		if y + 1 < map.len() && wall(x, y + 1) {
+			mask |= WALL_S;
+		}
+		if x > 0 && wall(x - 1, y) {
+			mask |= WALL_W;
+		}
+		mask
+	}
+
+	fn player_sprite(&self, on_store: bool) -> usize {
+		match (self.engine.snap.state.dir, on_store) {
+			(1, false) => N_PLAYERN,
+			(2, false) => N_PLAYERE,
+			(3, false) => N_PLAYERS,
+			(_, false) => N_PLAYERW,
+			(1, true) => N_SPLAYERN,
+			(2, true) => N_SPLAYERE,
+			(3, true) => N_SPLAYERS,
+			(_, true) => N_SPLAYERW,
+		}
+	}
+
+	/// Overlay the whole level in a corner when it doesn't fit in the window
+	fn draw_minimap(&self, canvas: &mut sdl2::render::Canvas<sdl2::video::Window>) {
+		let level = &self.engine.levels[self.engine.level];
+		let (w, h) = level.topology.extent(level.w, level.h);
+		let (map_width, map_height) = (self.map_width(), self.map_height());
+		let (width, height) = self.level_size();
+		if width <= map_width as f32 && height <= map_height as f32 {
+			return;
+		}
+		let ms = std::cmp::max(
+			1,
+			(map_width as f32 / MINIMAP_RATIO as f32 / w)
+				.min(map_height as f32 / MINIMAP_RATIO as f32 / h) as usize,
+		);
+		// minimap size
+		let (w, h) = ((w * ms as f32) as usize, (h * ms as f32) as usize);
+		// no room left for it in a tiny window, or next to the side panel
+		let mx = match map_width.checked_sub(w + ZOOM * 4) {
+			Some(mx) if h + ZOOM * 5 <= map_height => mx as i32,
+			_ => return,
+		};
+		let my = (ZOOM * 4) as i32;
+		canvas.set_blend_mode(BlendMode::Blend);
+		canvas.set_draw_color(Color::RGBA(0, 0, 0, 192));
+		canvas
+			.fill_rect(Rect::new(
+				mx - ZOOM as i32,
+				my - ZOOM as i32,
+				(w + 2 * ZOOM) as u32,
+				(h + 2 * ZOOM) as u32,
+			))
+			.expect("Couldn't fill rect");
+		canvas.set_blend_mode(BlendMode::None);
+		for (j, line) in self.engine.snap.state.map.iter().enumerate() {
+			for (i, &e) in line.iter().enumerate() {
+				let e = self.drawn(i, j, e);
+				let color = if self.engine.snap.state.workers.contains(&(i, j)) {
+					Color::RGB(0, 128, 255)
+				} else if e == WALL {
+					Color::RGB(160, 160, 160)
+				} else if e == CRATE | STORE {
+					Color::RGB(0, 192, 0)
+				} else if e == CRATE {
+					Color::RGB(192, 128, 0)
+				} else if e == STORE {
+					Color::RGB(255, 255, 0)
+				} else {
+					continue;
+				};
+				let (x, y) = level.topology.origin(i, j);
+				canvas.set_draw_color(color);
+				canvas
+					.fill_rect(Rect::new(
+						mx + (x * ms as f32) as i32,
+						my + (y * ms as f32) as i32,
+						ms as u32,
+						ms as u32,
+					))
+					.expect("Couldn't fill rect");
+			}
+		}
+		// visible part of the level
+		let vw = std::cmp::min(map_width * ms / self.bw, w);
+		let vh = std::cmp::min(map_height * ms / self.bh, h);
+		canvas.set_draw_color(Color::RGB(255, 255, 255));
+		canvas
+			.draw_rect(Rect::new(
+				mx + (self.camera.x * ms as f32 / self.bw as f32) as i32,
+				my + (self.camera.y * ms as f32 / self.bh as f32) as i32,
+				vw as u32,
+				vh as u32,
+			))
+			.expect("Couldn't draw rect");
+	}
+
+	/// HUD texts, most important first: the bottom bar shows as many as fit
+	fn hud_items(&self, state: &str) -> Vec<String> {
+		let engine = &self.engine;
+		let level = &engine.levels[engine.level];
+		let snap = &engine.snap;
+		let number = (engine.level + 1).to_string();
+		let mut items = Vec::new();
+		if !state.is_empty() {
+			items.push(state.to_string());
+		}
+		items.push(format!("Level {}/{}", number, engine.levels.len()));
+		items.push(format!("Moves {}", snap.state.moves));
+		let pushes = if engine.reverse { "Pulls" } else { "Pushes" };
+		items.push(format!("{} {}", pushes, snap.state.pushes));
+		items.push(format!("Crates {}/{}", snap.state.stored, level.crates));
+		if snap.state.workers.len() > 1 {
+			let workers = snap.state.workers.len();
+			items.push(format!("Worker {}/{}", snap.state.worker + 1, workers));
+		}
+		items.push(format!("Time {}", engine.clock()));
+		items.push(format!("Undos {}", snap.state.undos));
+		items.push(format!("Snapshots {}", engine.snapshots.len()));
+		if let Some(best) = engine.best_score() {
+			items.push(format!("Best {}/{}", best.moves, best.pushes));
+		}
+		// the bundled levels are only titled by their number
+		if !level.title.is_empty() && level.title != number {
+			items.push(level.title.clone());
+		}
+		items
+	}
+
+	/// Draw the HUD on the side panel, one item per line, or else on the bottom bar
+	fn draw_hud(
+		&self,
+		canvas: &mut sdl2::render::Canvas<sdl2::video::Window>,
+		texture_creator: &sdl2::render::TextureCreator<sdl2::video::WindowContext>,
+		items: &[String],
+	) {
+		let side_panel = self.side_panel();
+		let rect = if side_panel {
+			let x = self.map_width();
+			Rect::new(x as i32, 0, (self.width - x) as u32, self.height as u32)
+		} else {
+			let y = self.map_height();
+			Rect::new(0, y as i32, self.width as u32, (self.height - y) as u32)
+		};
+		canvas.set_draw_color(Color::RGB(255, 255, 255));
+		canvas.fill_rect(rect).expect("Couldn't fill rect");
+		canvas.set_clip_rect(rect);
+		let spacing = (TEXT_SIZE * ZOOM) as i32;
+		let (mut x, mut y) = (rect.x() + TEXT_SIZE as i32, rect.y());
+		if side_panel {
+			y += TEXT_SIZE as i32;
+		}
+		for item in items {
+			let (w, h) = self.font.size_of(item).expect("Cannot size text");
+			if !side_panel && x + w as i32 > rect.right() {
+				break;
+			}
+			let texture = create_texture_from_text(texture_creator, &self.font, item, 0, 0, 0)
 				.expect("Cannot render text");
+			let ty = if side_panel {
+				y
+			} else {
+				y + (rect.height() as i32 - h as i32) / 2
+			};
+			canvas
+				.copy(&texture, None, Some(Rect::new(x, ty, w, h)))
+				.expect("Couldn't copy text");
+			if side_panel {
+				y += h as i32;
+			} else {
+				x += w as i32 + spacing;
+			}
+		}
+		canvas.set_clip_rect(None);
+	}
+
+	/// Overlay text lines on top of the map, over a background of opacity `alpha`
+	fn draw_overlay(
+		&self,
+		canvas: &mut sdl2::render::Canvas<sdl2::video::Window>,
+		texture_creator: &sdl2::render::TextureCreator<sdl2::video::WindowContext>,
+		lines: &[String],
+		alpha: u8,
+	) {
+		canvas.set_blend_mode(BlendMode::Blend);
+		canvas.set_draw_color(Color::RGBA(0, 0, 0, alpha));
+		canvas
+			.fill_rect(Rect::new(
+				0,
+				0,
+				self.map_width() as u32,
+				self.map_height() as u32,
+			))
+			.expect("Couldn't fill rect");
+		canvas.set_blend_mode(BlendMode::None);
+		let mut x = (TEXT_SIZE * ZOOM) as i32;
+		let mut y = TEXT_SIZE as i32;
+		let mut column_width = 0;
+		for line in lines {
+			// empty lines can't be rendered, they only add space
+			if line.is_empty() {
+				y += TEXT_SIZE as i32;
+				continue;
+			}
+			let texture =
+				create_texture_from_text(texture_creator, &self.font, line, 255, 255, 255)
+					.expect("Cannot render text");
+			let query = texture.query();
+			// wrap to the next column at the bottom of the map
+			if y + query.height as i32 > self.map_height() as i32 {
+				x += column_width + (TEXT_SIZE * ZOOM) as i32;
+				y = TEXT_SIZE as i32;
+				column_width = 0;
+			}
 			canvas
 				.copy(
 					&texture,
 					None,
-					Some(Rect::new(
-						0,
-						(self.height - TEXT_SIZE * TEXT_RATIO - 4) as i32,
-						(text.len() * 5 * ZOOM) as u32,
-						(12 * ZOOM) as u32,
-					)),
+					Some(Rect::new(x, y, query.width, query.height)),
 				)
 				.expect("Couldn't copy text");
-			canvas.present();
-			self.must_draw = false;
+			column_width = column_width.max(query.width as i32);
+			y += query.height as i32;
 		}
 	}
 
-	fn handle_events(&mut self, event_pump: &mut sdl2::EventPump) {
+	fn handle_events(
+		&mut self,
+		event_pump: &mut sdl2::EventPump,
+		controller_subsystem: Option<&GameControllerSubsystem>,
+		canvas: &mut Canvas<Window>,
+	) {
 		for event in event_pump.poll_iter() {
 			match event {
 				Event::Quit { .. } => {
 					self.quit = true;
 					break;
 				}
-				Event::KeyDown {
-					keycode: Some(k), ..
-				} => match k {
-					Keycode::Escape => {
-						self.quit = true;
-						break;
-					}
-					Keycode::D => {
-						self.debug = !self.debug;
-						self.debug_dump();
-						continue;
+				Event::Window {
+					win_event: WindowEvent::Resized(..),
+					..
+				}
+				| Event::Window {
+					win_event: WindowEvent::SizeChanged(..),
+					..
+				} => {
+					self.resize(canvas);
+					continue;
+				}
+				Event::Window {
+					win_event: WindowEvent::FocusLost,
+					..
+				} => {
+					self.focused = false;
+					self.pause();
+					continue;
+				}
+				Event::Window {
+					win_event: WindowEvent::FocusGained,
+					..
+				} => {
+					self.focused = true;
+					continue;
+				}
+				Event::MouseWheel { y, .. } if y != 0 => {
+					self.zoom(y > 0);
+					continue;
+				}
+				Event::ControllerDeviceAdded { which, .. } => {
+					if let Some(subsystem) = controller_subsystem {
+						match subsystem.open(which) {
+							Ok(controller) => {
+								if self.engine.debug {
+									println!("Controller {} added", controller.name());
+								}
+								self.controllers.push(controller);
+							}
+							Err(e) => println!("Couldn't open controller {}: {}", which, e),
+						}
 					}
-					_ => {}
-				},
+					continue;
+				}
+				Event::ControllerDeviceRemoved { which, .. } => {
+					self.controllers.retain(|c| c.instance_id() != which);
+					self.pause();
+					continue;
+				}
+				_ => {}
+			}
+			match self.config.action(&event) {
+				Some(Action::Quit) => {
+					self.quit = true;
+					break;
+				}
+				Some(Action::Debug) => {
+					self.engine.debug = !self.engine.debug;
+					self.engine.debug_dump();
+					continue;
+				}
+				Some(Action::Help) => {
+					self.help = !self.help;
+					self.stats = false;
+					self.stop_play_input();
+					continue;
+				}
+				Some(Action::Stats) => {
+					self.stats = !self.stats;
+					self.help = false;
+					self.stop_play_input();
+					continue;
+				}
+				Some(Action::Fullscreen) => {
+					self.toggle_fullscreen(canvas);
+					continue;
+				}
+				Some(Action::Minimap) => {
+					self.minimap = !self.minimap;
+					self.must_draw = true;
+					continue;
+				}
+				Some(Action::Skin) => {
+					self.skin = (self.skin + 1) % self.skins.len();
+					self.must_draw = true;
+					continue;
+				}
+				Some(Action::VolumeUp) => {
+					self.audio.volume_up();
+					self.show_volume();
+					continue;
+				}
+				Some(Action::VolumeDown) => {
+					self.audio.volume_down();
+					self.show_volume();
+					continue;
+				}
+				Some(Action::Mute) => {
+					self.audio.toggle_mute();
+					self.show_volume();
+					continue;
+				}
+				Some(Action::ZoomIn) => {
+					self.zoom(true);
+					continue;
+				}
+				Some(Action::ZoomOut) => {
+					self.zoom(false);
+					continue;
+				}
 				_ => {}
 			}
-			if !match self.status {
-				Status::Play => self.handle_event_play(event),
-				Status::Pause => self.handle_event_pause(event),
+			if !match self.engine.status {
+				// no untimed moves behind the help or the statistics
+				Status::Play | Status::Pull if self.help || self.stats => true,
+				Status::Play | Status::Pull => self.handle_event_play(event),
+				Status::Pause => self.handle_event_pause(event, canvas),
 				Status::Win => self.handle_event_win(event),
 			} {
 				break;
@@ -708,88 +1309,252 @@ impl<'ttf> Game<'ttf> {
 
 	fn handle_event_play(&mut self, event: sdl2::event::Event) -> bool {
 		let mut cont = true;
-		if let Event::KeyDown {
-			keycode: Some(k), ..
-		} = event
-		{
-			match k {
-				Keycode::Space => {
-					self.status = Status::Pause;
+		match event {
+			Event::MouseButtonDown {
+				mouse_btn: MouseButton::Left,
+				x,
+				y,
+				..
+			} => {
+				if let Some((i, j)) = self.cell_at(x, y) {
+					let worker = self
+						.engine
+						.snap
+						.state
+						.workers
+						.iter()
+						.position(|&w| w == (i, j));
+					// crates are only pulled by walking away from them in pull mode
+					if let Some(worker) = worker {
+						self.command(Command::Switch(worker));
+					} else if self.engine.snap.state.map[j][i] & CRATE == CRATE
+						&& !self.engine.reverse
+					{
+						// the crate stays where it is picked up
+						self.commands.clear();
+						self.drag = Some((i, j, x, y));
+						self.must_draw = true;
+					} else {
+						self.walk_to(i, j);
+					}
+				}
+			}
+			Event::MouseMotion { x, y, .. } => {
+				if let Some((i, j, _, _)) = self.drag {
+					self.drag = Some((i, j, x, y));
+					self.must_draw = true;
+				}
+			}
+			Event::MouseButtonUp {
+				mouse_btn: MouseButton::Left,
+				x,
+				y,
+				..
+			} => {
+				if let Some((i, j, _, _)) = self.drag.take() {
+					match self.cell_at(x, y) {
+						Some(to) => self.push_to((i, j), to),
+						None => {
+							self.message =
+								Some(("Can't push the crate there".to_string(), Instant::now()))
+						}
+					}
 					self.must_draw = true;
+					if let Status::Win = self.engine.status {
+						cont = false;
+					}
+				}
+			}
+			_ => match self.config.action(&event) {
+				Some(Action::Pause) => {
+					self.pause();
 					cont = false;
 				}
-				Keycode::R => {
-					self.set_level(self.level);
+				Some(Action::Restart) => {
+					self.set_level(self.engine.level);
 					cont = false;
 				}
-				Keycode::W => {
-					self.status = Status::Win;
+				Some(Action::Reverse) => {
+					self.toggle_reverse();
+					cont = false;
+				}
+				Some(Action::Win) => {
+					self.engine.status = Status::Win;
 					self.must_draw = true;
 					cont = false;
 				}
-				Keycode::U => {
-					self.pop_undo();
+				Some(Action::Undo) => {
+					self.command(Command::Undo);
+				}
+				Some(Action::Redo) => {
+					self.command(Command::Redo);
 				}
-				Keycode::S => {
-					self.save_snapshot();
+				Some(Action::Save) => {
+					self.command(Command::Save);
 				}
-				Keycode::L => {
+				Some(Action::Load) => {
 					self.load_snapshot();
 				}
-				Keycode::Up => {
-					self.try_move(0, -1);
+				Some(Action::Up) => {
+					self.command(Command::Move(0, -1));
 				}
-				Keycode::Down => {
-					self.try_move(0, 1);
+				Some(Action::Down) => {
+					self.command(Command::Move(0, 1));
 				}
-				Keycode::Left => {
-					self.try_move(-1, 0);
+				Some(Action::Left) => {
+					self.command(Command::Move(-1, 0));
 				}
-				Keycode::Right => {
-					self.try_move(1, 0);
+				Some(Action::Right) => {
+					self.command(Command::Move(1, 0));
+				}
+				Some(Action::Worker) => {
+					self.command(Command::Worker);
+				}
+				Some(Action::UpLeft) => {
+					self.command(Command::Move(-1, -1));
+				}
+				Some(Action::UpRight) => {
+					self.command(Command::Move(1, -1));
+				}
+				Some(Action::DownLeft) => {
+					self.command(Command::Move(-1, 1));
+				}
+				Some(Action::DownRight) => {
+					self.command(Command::Move(1, 1));
 				}
 				_ => {}
-			}
+			},
 		}
 		cont
 	}
 
-	fn handle_event_pause(&mut self, event: sdl2::event::Event) -> bool {
+	fn handle_event_pause(
+		&mut self,
+		event: sdl2::event::Event,
+		canvas: &mut Canvas<Window>,
+	) -> bool {
 		let mut cont = true;
-		if let Event::KeyDown {
-			keycode: Some(k), ..
-		} = event
-		{
-			if let Keycode::Space = k {
-				self.status = Status::Play;
-				self.must_draw = true;
+		let levels = self.engine.levels.len();
+		match self.config.action(&event) {
+			Some(Action::Pause) => {
+				self.resume();
+				cont = false;
+			}
+			Some(Action::Up) => self.menu.select(-1, SETTINGS.len()),
+			Some(Action::Down) => self.menu.select(1, SETTINGS.len()),
+			Some(Action::Left) => match self.menu.settings {
+				Some(row) => self.change_setting(SETTINGS[row], false, canvas),
+				None => self.menu.choose_level(-1, levels),
+			},
+			Some(Action::Right) => match self.menu.settings {
+				Some(row) => self.change_setting(SETTINGS[row], true, canvas),
+				None => self.menu.choose_level(1, levels),
+			},
+			Some(Action::Next) => {
+				match (self.menu.settings, self.menu.entry()) {
+					(Some(row), _) => self.change_setting(SETTINGS[row], true, canvas),
+					(None, Entry::Resume) => self.resume(),
+					(None, Entry::Restart) => {
+						self.set_level(self.engine.level);
+					}
+					(None, Entry::Level) => {
+						self.set_level(self.menu.level);
+					}
+					(None, Entry::Settings) => self.menu.settings = Some(0),
+					(None, Entry::Quit) => self.quit = true,
+				}
 				cont = false;
 			}
+			_ => {}
 		}
+		self.must_draw = true;
 		cont
 	}
 
+	/// Apply a settings row: `forward` is Right or Return, as opposed to Left
+	fn change_setting(&mut self, setting: Setting, forward: bool, canvas: &mut Canvas<Window>) {
+		match setting {
+			Setting::Skin => {
+				let n = self.skins.len();
+				self.skin = if forward {
+					(self.skin + 1) % n
+				} else {
+					(self.skin + n - 1) % n
+				};
+			}
+			Setting::Volume if forward => self.audio.volume_up(),
+			Setting::Volume => self.audio.volume_down(),
+			Setting::Sound => self.audio.toggle_mute(),
+			Setting::Fullscreen => self.toggle_fullscreen(canvas),
+			Setting::Back => self.menu.settings = None,
+		}
+	}
+
+	/// Rows of the settings page
+	fn settings_lines(&self, fullscreen: bool) -> Vec<String> {
+		let on_off = |on: bool| if on { "on" } else { "off" };
+		SETTINGS
+			.iter()
+			.enumerate()
+			.map(|(i, setting)| {
+				let label = match setting {
+					Setting::Skin => format!("Skin < {} >", self.skins[self.skin]),
+					Setting::Volume => format!("Volume < {}% >", self.audio.volume()),
+					Setting::Sound => format!("Sound {}", on_off(!self.audio.muted())),
+					Setting::Fullscreen => format!("Fullscreen {}", on_off(fullscreen)),
+					Setting::Back => "Back".to_string(),
+				};
+				menu::row(self.menu.settings == Some(i), &label)
+			})
+			.collect()
+	}
+
+	/// Level info, menu and key bindings shown while paused
+	fn pause_lines(&self, fullscreen: bool) -> Vec<String> {
+		let engine = &self.engine;
+		let level = &engine.levels[engine.level];
+		let mut lines = vec![
+			"*PAUSE*".to_string(),
+			format!(
+				"Level {}/{}: {}/{} crates stored",
+				engine.level + 1,
+				engine.levels.len(),
+				engine.snap.state.stored,
+				level.crates
+			),
+		];
+		if let Some(best) = engine.best_score() {
+			lines.push(format!(
+				"Best: {} moves, {} pushes, {}s",
+				best.moves, best.pushes, best.time_s
+			));
+		}
+		lines.push(String::new());
+		match self.menu.settings {
+			Some(_) => lines.extend(self.settings_lines(fullscreen)),
+			None => lines.extend(self.menu.lines(engine.levels.len())),
+		}
+		lines.push(String::new());
+		lines.extend(self.config.help());
+		lines
+	}
+
 	fn handle_event_win(&mut self, event: sdl2::event::Event) -> bool {
 		let mut cont = true;
-		if let Event::KeyDown {
-			keycode: Some(k), ..
-		} = event
-		{
-			match k {
-				Keycode::Return => {
-					if self.set_level(self.level + 1) {
-					} else {
-						println!("Game over.");
-						self.quit = true;
-						cont = false;
-					}
-				}
-				Keycode::R => {
-					self.set_level(self.level);
+		match self.config.action(&event) {
+			Some(Action::Next) => {
+				if self.set_level(self.engine.level + 1) {
+				} else {
+					println!("Game over.");
+					self.quit = true;
 					cont = false;
 				}
-				_ => {}
 			}
+			Some(Action::Restart) => {
+				self.set_level(self.engine.level);
+				cont = false;
+			}
+			_ => {}
 		}
 		cont
 	}
@@ -799,6 +1564,29 @@ impl<'ttf> Game<'ttf> {
 	}
 }
 
+/// Fill the convex polygon `corners` with the draw color, one row of pixels at a time
+fn fill_polygon(canvas: &mut Canvas<Window>, corners: &[Point]) {
+	let top = corners.iter().map(|p| p.y()).min().unwrap_or(0);
+	let bottom = corners.iter().map(|p| p.y()).max().unwrap_or(0);
+	for y in top..bottom {
+		let fy = y as f32 + 0.5;
+		let (mut left, mut right) = (f32::MAX, f32::MIN);
+		for (a, b) in corners.iter().zip(corners.iter().cycle().skip(1)) {
+			if (a.y() as f32 <= fy) != (b.y() as f32 <= fy) {
+				let t = (fy - a.y() as f32) / (b.y() - a.y()) as f32;
+				let x = a.x() as f32 + t * (b.x() - a.x()) as f32;
+				left = left.min(x);
+				right = right.max(x);
+			}
+		}
+		if left < right {
+			canvas
+				.draw_line((left.round() as i32, y), (right.round() as i32 - 1, y))
+				.expect("Couldn't draw line");
+		}
+	}
+}
+
 fn create_texture_from_text<'a>(
 	texture_creator: &'a TextureCreator<WindowContext>,
 	font: &sdl2::ttf::Font,
@@ -814,108 +1602,64 @@ fn create_texture_from_text<'a>(
 	}
 }
 
-fn create_texture_rect<'a>(
-	canvas: &mut Canvas<Window>,
-	texture_creator: &'a TextureCreator<WindowContext>,
-	r: u8,
-	g: u8,
-	b: u8,
-	width: u32,
-	height: u32,
-) -> Option<Texture<'a>> {
-	if let Ok(mut square_texture) = texture_creator.create_texture_target(None, width, height) {
-		canvas
-			.with_texture_canvas(&mut square_texture, |texture| {
-				texture.set_draw_color(Color::RGB(r, g, b));
-				texture.clear();
-			})
-			.expect("Failed to color a texture");
-		Some(square_texture)
-	} else {
-		None
-	}
-}
-
-fn load_texture<'a>(
-	root_dir: &std::path::Path,
-	texture_creator: &'a TextureCreator<WindowContext>,
-	file: &str,
-) -> Option<Texture<'a>> {
-	let file = root_dir.join("res").join("images").join(file);
-	let file = file.to_str().unwrap();
-	Some(texture_creator.load_texture(file).unwrap())
-}
-
 fn main() {
-	let width = WIDTH;
-	let height = HEIGHT;
+	let options = Options::parse(std::env::args());
+	if let Some(tool) = &options.tool {
+		tools::run(tool, &options);
+	}
 	let sdl_context = sdl2::init().expect("SDL initialization failed");
 	let ttf_context = sdl2::ttf::init().expect("SDL TTF initialization failed");
 	let _image_context = sdl2::image::init(InitFlag::PNG).unwrap();
 	let video_subsystem = sdl_context
 		.video()
 		.expect("Couldn't get SDL video subsystem");
-	let window = video_subsystem
-		.window(TITLE, width as u32, height as u32)
+	let mut window = video_subsystem
+		.window(TITLE, options.width as u32, options.height as u32)
 		.position_centered()
+		.resizable()
+		.allow_highdpi()
 		.build()
 		.expect("Failed to create window");
+	if options.fullscreen {
+		if let Err(e) = window.set_fullscreen(FullscreenType::Desktop) {
+			println!("Couldn't set fullscreen: {}", e);
+		}
+	}
 	let mut canvas = window
 		.into_canvas()
 		.target_texture()
 		.present_vsync()
 		.build()
 		.expect("Couldn't get window's canvas");
+	let controller_subsystem = sdl_context
+		.game_controller()
+		.map_err(|e| println!("Couldn't get SDL game controller subsystem: {}", e))
+		.ok();
 	let mut event_pump = sdl_context.event_pump().expect(
 		"Failed to get
           SDL event pump",
 	);
 	let texture_creator: TextureCreator<_> = canvas.texture_creator();
-	let root_dir = current_exe().unwrap();
-	let root_dir = root_dir
-		.parent()
-		.unwrap()
-		.parent()
-		.unwrap()
-		.parent()
-		.unwrap();
-	let mut game = Game::new(&ttf_context, root_dir, width, height);
-	macro_rules! texture {
-		($r:expr, $g:expr, $b:expr) => {
-			create_texture_rect(
-				&mut canvas,
-				&texture_creator,
-				$r,
-				$g,
-				$b,
-				game.bw as u32,
-				game.bh as u32,
-			)
-			.unwrap()
-		};
-		($file:expr) => {
-			load_texture(root_dir, &texture_creator, $file).unwrap()
-		};
-	}
-	let textures = [
-		texture!(I_EMPTY),
-		texture!(I_STORE),
-		texture!(I_STORED),
-		texture!(I_CRATE),
-		texture!(I_WALL),
-		texture!(I_PLAYERW),
-		texture!(I_SPLAYERW),
-		texture!(I_PLAYERN),
-		texture!(I_SPLAYERN),
-		texture!(I_PLAYERE),
-		texture!(I_SPLAYERE),
-		texture!(I_PLAYERS),
-		texture!(I_SPLAYERS),
-		texture!(255, 255, 255),
-	];
+	let mut game = Game::new(&ttf_context, &options);
+	game.resize(&canvas);
+	let res_dir = options.res_dir.as_path();
+	let mut skin = Skin::bundled(res_dir, &texture_creator).expect("Couldn't load the images");
+	let mut skin_loaded = None;
 	while !game.quit {
-		game.handle_events(&mut event_pump);
-		game.draw_map(&mut canvas, &textures, &texture_creator);
+		game.handle_events(&mut event_pump, controller_subsystem.as_ref(), &mut canvas);
+		if skin_loaded != Some(game.skin) {
+			match Skin::load(res_dir, &texture_creator, &game.skins[game.skin]) {
+				Ok(s) => skin = s,
+				Err(e) => {
+					println!("{}", e);
+					game.message = Some((e, Instant::now()));
+				}
+			}
+			game.set_tile_size(skin.tile_size());
+			skin_loaded = Some(game.skin);
+		}
+		game.draw_map(&mut canvas, &mut skin, &texture_creator);
 		game.sleep();
 	}
+	game.engine.leave_attempt(Outcome::Left);
 }
@@ -0,0 +1,79 @@
+/// Entries of the pause menu
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Entry {
+	Resume,
+	Restart,
+	Level,
+	Settings,
+	Quit,
+}
+
+const ENTRIES: [Entry; 5] = [
+	Entry::Resume,
+	Entry::Restart,
+	Entry::Level,
+	Entry::Settings,
+	Entry::Quit,
+];
+
+/// Pause menu state, shared by the frontends which draw it and own the settings rows
+pub struct PauseMenu {
+	pub selected: usize,         // row of the main page
+	pub level: usize,            // level chosen by the Level entry
+	pub settings: Option<usize>, // row of the settings page, when open
+}
+
+/// Menu row with its selection marker
+pub fn row(selected: bool, label: &str) -> String {
+	format!("{} {}", if selected { '>' } else { ' ' }, label)
+}
+
+impl PauseMenu {
+	pub fn new(level: usize) -> PauseMenu {
+		PauseMenu {
+			selected: 0,
+			level,
+			settings: None,
+		}
+	}
+
+	pub fn entry(&self) -> Entry {
+		ENTRIES[self.selected]
+	}
+
+	/// Move the selection `delta` rows, wrapping around; the settings page has
+	/// `settings_rows` rows
+	pub fn select(&mut self, delta: isize, settings_rows: usize) {
+		let step =
+			|row: usize, rows: usize| (row as isize + delta).rem_euclid(rows as isize) as usize;
+		match self.settings {
+			Some(row) => self.settings = Some(step(row, settings_rows)),
+			None => self.selected = step(self.selected, ENTRIES.len()),
+		}
+	}
+
+	/// Choose the level `delta` levels away when the Level entry is selected
+	pub fn choose_level(&mut self, delta: isize, levels: usize) {
+		if self.settings.is_none() && self.entry() == Entry::Level {
+			self.level = (self.level as isize + delta).clamp(0, levels as isize - 1) as usize;
+		}
+	}
+
+	/// Rows of the main page
+	pub fn lines(&self, levels: usize) -> Vec<String> {
+		ENTRIES
+			.iter()
+			.enumerate()
+			.map(|(i, entry)| {
+				let label = match entry {
+					Entry::Resume => "Resume".to_string(),
+					Entry::Restart => "Restart level".to_string(),
+					Entry::Level => format!("Go to level < {} / {} >", self.level + 1, levels),
+					Entry::Settings => "Settings".to_string(),
+					Entry::Quit => "Quit".to_string(),
+				};
+				row(i == self.selected, &label)
+			})
+			.collect()
+	}
+}
@@ -0,0 +1,244 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::color;
+use crate::path::{self, DIRS};
+use crate::solver::{area, frozen, lurd_move, walk_and_push, with_crates};
+use crate::tile;
+use crate::topology::Topology;
+use crate::{Engine, Level, Map, Moved, Status, CRATE, WALL};
+
+const WINDOW: usize = 6; // longest run of pushes searched for a shorter one
+const MAX_STATES: usize = 20_000; // give up beyond that many positions per search
+
+type Push = (usize, usize, usize); // crate position and DIRS index
+type Key = (Vec<(usize, usize)>, (usize, usize)); // sorted crates and player area
+type Position = (Vec<(usize, usize)>, (usize, usize)); // crates and player
+
+/// Moves and pushes of a LURD solution
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Metrics {
+	pub moves: usize,
+	pub pushes: usize,
+}
+
+impl Metrics {
+	pub fn of(lurd: &str) -> Metrics {
+		// worker switches aren't moves
+		let moves: Vec<char> = lurd.chars().filter(char::is_ascii_alphabetic).collect();
+		Metrics {
+			moves: moves.len(),
+			pushes: moves.iter().filter(|c| c.is_ascii_uppercase()).count(),
+		}
+	}
+}
+
+/// Replay `lurd` through the engine, as the player would, and collect its pushes
+fn replay(level: &Level, lurd: &str) -> Result<Vec<Push>, String> {
+	let mut engine = Engine::offline(vec![level.clone()]);
+	engine.set_level(0);
+	let mut pushes = Vec::new();
+	for (i, c) in lurd.chars().filter(|c| !c.is_whitespace()).enumerate() {
+		let (dx, dy) = lurd_move(c).ok_or_else(|| format!("Invalid move [{}] at {}", c, i + 1))?;
+		if matches!(engine.status, Status::Win) {
+			return Err(format!("Moves after the win at {}", i + 1));
+		}
+		match engine.try_move(dx, dy) {
+			None => return Err(format!("Blocked move [{}] at {}", c, i + 1)),
+			Some(Moved::Step) => {}
+			Some(_) => {
+				let d = DIRS.iter().position(|&dir| dir == (dx, dy)).unwrap();
+				pushes.push((engine.snap.state.px, engine.snap.state.py, d));
+			}
+		}
+	}
+	match engine.status {
+		Status::Win => Ok(pushes),
+		_ => Err("Not a solution, some crates aren't stored".to_string()),
+	}
+}
+
+fn key(base: &Map, crates: &[(usize, usize)], player: (usize, usize)) -> Key {
+	let mut crates = crates.to_vec();
+	crates.sort_unstable();
+	let reach = path::reachable(Topology::Square, &with_crates(base, &crates), player);
+	(crates, area(&reach))
+}
+
+/// Crates and player after each push, starting with the level itself
+fn positions(level: &Level, pushes: &[Push]) -> Vec<Position> {
+	let mut crates = Vec::new();
+	for (j, line) in level.map.iter().enumerate() {
+		for (i, &e) in line.iter().enumerate() {
+			if e & CRATE == CRATE {
+				crates.push((i, j));
+			}
+		}
+	}
+	let mut positions = vec![(crates.clone(), (level.px, level.py))];
+	for &(cx, cy, d) in pushes {
+		let (dx, dy) = DIRS[d];
+		let k = crates
+			.iter()
+			.position(|&c| c == (cx, cy))
+			.expect("No crate to push");
+		crates[k] = ((cx as isize + dx) as usize, (cy as isize + dy) as usize);
+		positions.push((crates.clone(), (cx, cy)));
+	}
+	positions
+}
+
+/// Positions reachable from `crates` and `player` in fewer than WINDOW pushes, with the
+/// fewest pushes leading to each
+fn search(
+	base: &Map,
+	crates: &[(usize, usize)],
+	player: (usize, usize),
+) -> HashMap<Key, Vec<Push>> {
+	let start = key(base, crates, player);
+	let mut found = HashMap::new();
+	let mut queue = VecDeque::new();
+	queue.push_back((start.0.clone(), player, Vec::new()));
+	found.insert(start, Vec::new());
+	while let Some((crates, player, chain)) = queue.pop_front() {
+		if found.len() >= MAX_STATES {
+			break;
+		}
+		if chain.len() + 1 >= WINDOW {
+			continue;
+		}
+		let map = with_crates(base, &crates);
+		let reach = path::reachable(Topology::Square, &map, player);
+		for (k, &(cx, cy)) in crates.iter().enumerate() {
+			for (d, &(dx, dy)) in DIRS.iter().enumerate() {
+				match path::step(&map, cx, cy, -dx, -dy) {
+					Some((sx, sy)) if reach[sy][sx] => {}
+					_ => continue,
+				}
+				let to = match path::step(&map, cx, cy, dx, dy) {
+					Some((tx, ty)) if map[ty][tx] & (WALL | CRATE) == 0 => (tx, ty),
+					_ => continue,
+				};
+				let mut after = map.clone();
+				after[cy][cx] &= !CRATE;
+				after[to.1][to.0] |= CRATE;
+				if frozen(&after, to.0, to.1) {
+					continue;
+				}
+				let mut next = crates.clone();
+				next[k] = to;
+				let next_key = key(base, &next, (cx, cy));
+				if found.contains_key(&next_key) {
+					continue;
+				}
+				let mut next_chain = chain.clone();
+				next_chain.push((cx, cy, d));
+				queue.push_back((next_key.0.clone(), (cx, cy), next_chain.clone()));
+				found.insert(next_key, next_chain);
+			}
+		}
+	}
+	found
+}
+
+/// Improve a LURD solution of `level`: replace runs of pushes by fewer pushes reaching the
+/// same position, which may reorder them, then walk the shortest paths between the pushes
+pub fn optimize(level: &Level, lurd: &str) -> Result<String, String> {
+	if level.topology != Topology::Square {
+		return Err("LURD moves need a square grid".to_string());
+	}
+	if level.workers.len() > 1 || tile::any(&level.tiles) || color::any(level) {
+		return Err(
+			"Only single worker solutions over classic cells and crates can be optimized"
+				.to_string(),
+		);
+	}
+	let mut pushes = replay(level, lurd)?;
+	let mut base = level.map.clone();
+	for line in base.iter_mut() {
+		for e in line.iter_mut() {
+			*e &= !CRATE;
+		}
+	}
+	let mut i = 0;
+	while i + 1 < pushes.len() {
+		let positions = positions(level, &pushes);
+		let (crates, player) = &positions[i];
+		let found = search(&base, crates, *player);
+		let better = (i + 2..=pushes.len().min(i + WINDOW))
+			.filter_map(|j| {
+				let (crates, player) = &positions[j];
+				found
+					.get(&key(&base, crates, *player))
+					.filter(|chain| chain.len() < j - i)
+					.map(|chain| (j, chain))
+			})
+			.max_by_key(|(j, chain)| j - i - chain.len());
+		match better {
+			// stay at i, the new pushes may shorten further
+			Some((j, chain)) => {
+				pushes.splice(i..j, chain.clone());
+			}
+			None => i += 1,
+		}
+	}
+	let optimized = walk_and_push(level, &pushes);
+	replay(level, &optimized).expect("The optimized solution doesn't solve the level");
+	let (before, after) = (Metrics::of(lurd), Metrics::of(&optimized));
+	if (after.pushes, after.moves) < (before.pushes, before.moves) {
+		Ok(optimized)
+	} else {
+		Ok(lurd.chars().filter(|c| !c.is_whitespace()).collect())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::valid_levels;
+
+	#[test]
+	fn optimize_drops_the_detours() {
+		let level = &valid_levels("#######\n#@$  .#\n#     #\n#######\n")[0];
+		assert_eq!(optimize(level, "RlrRR"), Ok("RRR".to_string()));
+		assert_eq!(
+			Metrics::of("R[2]uL"),
+			Metrics {
+				moves: 3,
+				pushes: 2
+			}
+		);
+	}
+
+	#[test]
+	fn optimize_keeps_the_best_solutions() {
+		let level = &valid_levels("######\n#@$ .#\n######\n")[0];
+		assert_eq!(optimize(level, "R R"), Ok("RR".to_string()));
+	}
+
+	#[test]
+	fn optimize_rejects_what_does_not_solve() {
+		let level = &valid_levels("######\n#@$ .#\n######\n")[0];
+		assert_eq!(
+			optimize(level, "R"),
+			Err("Not a solution, some crates aren't stored".to_string())
+		);
+		assert_eq!(
+			optimize(level, "L"),
+			Err("Blocked move [L] at 1".to_string())
+		);
+		assert!(optimize(level, "Rx").is_err());
+	}
+
+	#[test]
+	fn optimize_rejects_moves_after_the_win() {
+		let level = &valid_levels("########\n#@$ .  #\n########\n")[0];
+		assert_eq!(
+			optimize(level, "RRR"),
+			Err("Moves after the win at 3".to_string())
+		);
+		assert_eq!(
+			optimize(level, "RRRlLL"),
+			Err("Moves after the win at 3".to_string())
+		);
+	}
+}
@@ -0,0 +1,284 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::topology::Topology;
+use crate::{Map, CRATE, EMPTY, STORE, WALL};
+
+pub(crate) const DIRS: [(isize, isize); 4] = [(-1, 0), (0, -1), (1, 0), (0, 1)];
+
+fn is_free(map: &Map, x: usize, y: usize) -> bool {
+	let e = map[y][x];
+	e == EMPTY || e == STORE
+}
+
+pub(crate) fn step(map: &Map, x: usize, y: usize, dx: isize, dy: isize) -> Option<(usize, usize)> {
+	let x = x as isize + dx;
+	let y = y as isize + dy;
+	if x < 0 || y < 0 || y as usize >= map.len() || x as usize >= map[y as usize].len() {
+		return None;
+	}
+	Some((x as usize, y as usize))
+}
+
+type Step = (usize, usize, isize, isize); // cell and move from it
+
+/// Find the shortest walk (without pushing) from `from` to `to`, as a list of unit moves
+pub fn find_path(
+	topology: Topology,
+	map: &Map,
+	from: (usize, usize),
+	to: (usize, usize),
+) -> Option<Vec<(isize, isize)>> {
+	walk(topology, map, from, to, false)
+}
+
+/// Find the shortest walk in pull mode, where no move may leave a crate right behind the
+/// player, as it would pull it along
+pub fn find_pull_free_path(
+	topology: Topology,
+	map: &Map,
+	from: (usize, usize),
+	to: (usize, usize),
+) -> Option<Vec<(isize, isize)>> {
+	walk(topology, map, from, to, true)
+}
+
+fn walk(
+	topology: Topology,
+	map: &Map,
+	from: (usize, usize),
+	to: (usize, usize),
+	pulling: bool,
+) -> Option<Vec<(isize, isize)>> {
+	if to.1 >= map.len() || to.0 >= map[to.1].len() || !is_free(map, to.0, to.1) {
+		return None;
+	}
+	// prev[y][x] holds the cell and the move that reached x:y
+	let mut prev: Vec<Vec<Option<Step>>> = map.iter().map(|line| vec![None; line.len()]).collect();
+	let mut seen: Vec<Vec<bool>> = map.iter().map(|line| vec![false; line.len()]).collect();
+	let mut queue = VecDeque::new();
+	seen[from.1][from.0] = true;
+	queue.push_back(from);
+	while let Some((x, y)) = queue.pop_front() {
+		if (x, y) == to {
+			let mut moves = Vec::new();
+			let (mut x, mut y) = to;
+			while let Some((px, py, dx, dy)) = prev[y][x] {
+				moves.push((dx, dy));
+				x = px;
+				y = py;
+			}
+			moves.reverse();
+			return Some(moves);
+		}
+		for &(dx, dy) in topology.moves() {
+			let pulls = pulling
+				&& topology
+					.step(map, x, y, -dx, -dy)
+					.is_some_and(|(bx, by)| map[by][bx] & CRATE == CRATE);
+			if pulls {
+				continue;
+			}
+			if let Some((nx, ny)) = topology.step(map, x, y, dx, dy) {
+				if !seen[ny][nx] && is_free(map, nx, ny) {
+					seen[ny][nx] = true;
+					prev[ny][nx] = Some((x, y, dx, dy));
+					queue.push_back((nx, ny));
+				}
+			}
+		}
+	}
+	None
+}
+
+/// Mark all cells reachable from `from` through cells accepted by `pass`
+fn flood(
+	topology: Topology,
+	map: &Map,
+	from: (usize, usize),
+	pass: fn(u8) -> bool,
+) -> Vec<Vec<bool>> {
+	let mut seen: Vec<Vec<bool>> = map.iter().map(|line| vec![false; line.len()]).collect();
+	let mut queue = VecDeque::new();
+	seen[from.1][from.0] = true;
+	queue.push_back(from);
+	while let Some((x, y)) = queue.pop_front() {
+		for &(dx, dy) in topology.moves() {
+			if let Some((nx, ny)) = topology.step(map, x, y, dx, dy) {
+				if !seen[ny][nx] && pass(map[ny][nx]) {
+					seen[ny][nx] = true;
+					queue.push_back((nx, ny));
+				}
+			}
+		}
+	}
+	seen
+}
+
+/// Mark all cells reachable by walking from `from`
+pub(crate) fn reachable(topology: Topology, map: &Map, from: (usize, usize)) -> Vec<Vec<bool>> {
+	flood(topology, map, from, |e| e == EMPTY || e == STORE)
+}
+
+/// Mark the cells enclosed by walls along with `from`, whatever crates stand in the way
+pub fn interior(topology: Topology, map: &Map, from: (usize, usize)) -> Vec<Vec<bool>> {
+	flood(topology, map, from, |e| e & WALL != WALL)
+}
+
+// A push node is a crate position plus the move pushing it, the player standing behind
+type Node = (usize, usize, usize);
+
+/// Queue the push nodes around crate `c` that the player at `p` can walk to
+fn queue_sides(
+	topology: Topology,
+	map: &mut Map,
+	c: (usize, usize),
+	p: (usize, usize),
+	parent: Option<Node>,
+	parents: &mut HashMap<Node, Option<Node>>,
+	queue: &mut VecDeque<Node>,
+) {
+	map[c.1][c.0] |= CRATE;
+	let seen = reachable(topology, map, p);
+	map[c.1][c.0] &= !CRATE;
+	for (d, &(dx, dy)) in topology.moves().iter().enumerate() {
+		if let Some((sx, sy)) = topology.step(map, c.0, c.1, -dx, -dy) {
+			let node = (c.0, c.1, d);
+			if seen[sy][sx] && !parents.contains_key(&node) {
+				parents.insert(node, parent);
+				queue.push_back(node);
+			}
+		}
+	}
+}
+
+/// Find the moves (walks and pushes) that bring the crate at `from` to `to`, without
+/// moving any other crate
+pub fn find_push_path(
+	topology: Topology,
+	map: &Map,
+	player: (usize, usize),
+	from: (usize, usize),
+	to: (usize, usize),
+) -> Option<Vec<(isize, isize)>> {
+	if map[from.1][from.0] & CRATE != CRATE {
+		return None;
+	}
+	if from == to {
+		return Some(Vec::new());
+	}
+	let mut map = map.clone();
+	map[from.1][from.0] &= !CRATE;
+	let mut parents = HashMap::new();
+	let mut queue = VecDeque::new();
+	queue_sides(
+		topology,
+		&mut map,
+		from,
+		player,
+		None,
+		&mut parents,
+		&mut queue,
+	);
+	let mut last = None;
+	while let Some(node) = queue.pop_front() {
+		let (dx, dy) = topology.moves()[node.2];
+		if let Some(t) = topology.step(&map, node.0, node.1, dx, dy) {
+			if !is_free(&map, t.0, t.1) {
+				continue;
+			}
+			if t == to {
+				last = Some(node);
+				break;
+			}
+			let c = (node.0, node.1);
+			queue_sides(
+				topology,
+				&mut map,
+				t,
+				c,
+				Some(node),
+				&mut parents,
+				&mut queue,
+			);
+		}
+	}
+	let mut chain = Vec::new();
+	while let Some(n) = last {
+		chain.push(n);
+		last = parents[&n];
+	}
+	if chain.is_empty() {
+		return None;
+	}
+	chain.reverse();
+	let mut moves = Vec::new();
+	let mut p = player;
+	for (cx, cy, d) in chain {
+		let (dx, dy) = topology.moves()[d];
+		let side = topology.step(&map, cx, cy, -dx, -dy)?;
+		map[cy][cx] |= CRATE;
+		moves.extend(find_path(topology, &map, p, side)?);
+		map[cy][cx] &= !CRATE;
+		moves.push((dx, dy));
+		p = (cx, cy);
+	}
+	Some(moves)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{valid_levels, Engine, Status};
+
+	#[test]
+	fn find_path_walks_around_walls() {
+		let level = &valid_levels("#####\n#@# #\n#   #\n#####\n")[0];
+		let moves = find_path(Topology::Square, &level.map, (1, 1), (3, 1)).unwrap();
+		assert_eq!(moves, vec![(0, 1), (1, 0), (1, 0), (0, -1)]);
+		assert_eq!(
+			find_path(Topology::Square, &level.map, (1, 1), (2, 1)),
+			None
+		);
+	}
+
+	#[test]
+	fn find_pull_free_path_leaves_the_crates() {
+		let level = &valid_levels("#####\n#  $#\n#$@ #\n#.. #\n#####\n")[0];
+		let map = &level.map;
+		assert_eq!(
+			find_path(Topology::Square, map, (2, 2), (3, 2)),
+			Some(vec![(1, 0)])
+		);
+		let moves = find_pull_free_path(Topology::Square, map, (2, 2), (3, 2)).unwrap();
+		assert_eq!(moves.len(), 3);
+		assert_eq!(
+			find_pull_free_path(Topology::Square, map, (2, 2), (1, 1)),
+			None
+		);
+	}
+
+	#[test]
+	fn find_push_path_stores_the_crate() {
+		let levels = valid_levels("######\n#    #\n#@$ .#\n#    #\n######\n");
+		let level = &levels[0];
+		let moves = find_push_path(Topology::Square, &level.map, (1, 2), (2, 2), (4, 2)).unwrap();
+		let mut engine = Engine::offline(levels.clone());
+		for (dx, dy) in moves {
+			assert!(engine.try_move(dx, dy).is_some());
+		}
+		assert!(matches!(engine.status, Status::Win));
+	}
+
+	#[test]
+	fn find_push_path_fails_into_a_corner() {
+		let level = &valid_levels("#####\n#@$.#\n#####\n")[0];
+		assert_eq!(
+			find_push_path(Topology::Square, &level.map, (1, 1), (2, 1), (1, 1)),
+			None
+		);
+		assert_eq!(
+			find_push_path(Topology::Square, &level.map, (1, 1), (3, 1), (2, 1)),
+			None
+		);
+	}
+}
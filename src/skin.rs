@@ -0,0 +1,261 @@
+use sdl2::image::{ImageRWops, LoadTexture};
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{Canvas, Texture, TextureCreator};
+use sdl2::rwops::RWops;
+use sdl2::video::{Window, WindowContext};
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use cratesan::assets;
+
+use crate::{
+	I_CRATE, I_EMPTY, I_PLAYERE, I_PLAYERN, I_PLAYERS, I_PLAYERW, I_SPLAYERE, I_SPLAYERN,
+	I_SPLAYERS, I_SPLAYERW, I_STORE, I_STORED, I_WALL, N_WALL, ZOOM,
+};
+
+pub const DEFAULT_SKIN: &str = "default";
+const SKINS_DIR: &str = "skins";
+const MANIFEST_FILE: &str = "skin.txt";
+const WALL_PREFIX: &str = "wall_";
+
+/// Bits of the wall neighbors mask
+pub const WALL_N: usize = 1;
+pub const WALL_E: usize = 2;
+pub const WALL_S: usize = 4;
+pub const WALL_W: usize = 8;
+const WALL_VARIANTS: usize = 16;
+
+/// Bundled image of each tile role, in N_* order; roles are named after the image stems
+const ROLES: [&str; 13] = [
+	I_EMPTY, I_STORE, I_STORED, I_CRATE, I_WALL, I_PLAYERW, I_SPLAYERW, I_PLAYERN, I_SPLAYERN,
+	I_PLAYERE, I_SPLAYERE, I_PLAYERS, I_SPLAYERS,
+];
+
+type Tile = (usize, Option<Rect>); // texture index and source rect
+
+/// Set of textures drawing each tile role, possibly as parts of a tilesheet
+pub struct Skin<'a> {
+	textures: Vec<Texture<'a>>,
+	tiles: Vec<Tile>,
+	walls: Vec<Option<Tile>>, // optional wall variant for each neighbors mask
+}
+
+fn role_name(image: &str) -> &str {
+	image.trim_end_matches(".png")
+}
+
+fn load<'a>(
+	texture_creator: &'a TextureCreator<WindowContext>,
+	file: &Path,
+) -> Result<Texture<'a>, String> {
+	texture_creator
+		.load_texture(file)
+		.map_err(|e| format!("Couldn't load {}: {}", file.display(), e))
+}
+
+/// Load a bundled image from `res_dir`, or from its embedded copy if the file is missing
+fn load_bundled<'a>(
+	texture_creator: &'a TextureCreator<WindowContext>,
+	res_dir: &Path,
+	image: &str,
+) -> Result<Texture<'a>, String> {
+	let name = format!("images/{}", image);
+	let file = res_dir.join(&name);
+	match assets::embedded(&name) {
+		Some(bytes) if !file.is_file() => {
+			let surface = RWops::from_bytes(bytes)?.load()?;
+			texture_creator
+				.create_texture_from_surface(&surface)
+				.map_err(|e| format!("Couldn't load {}: {}", name, e))
+		}
+		_ => load(texture_creator, &file),
+	}
+}
+
+/// Parse "AxB" (tile size) or "A,B" (tile position) pairs
+fn pair(value: &str, sep: char) -> Option<(u32, u32)> {
+	let mut iter = value.split(sep).map(|v| v.trim().parse::<u32>());
+	match (iter.next(), iter.next(), iter.next()) {
+		(Some(Ok(a)), Some(Ok(b)), None) => Some((a, b)),
+		_ => None,
+	}
+}
+
+/// Names of the bundled skin and of the skin packs found in the resources
+pub fn list(res_dir: &Path) -> Vec<String> {
+	let mut skins = Vec::new();
+	if let Ok(entries) = std::fs::read_dir(res_dir.join(SKINS_DIR)) {
+		for entry in entries.flatten() {
+			if entry.path().join(MANIFEST_FILE).is_file() {
+				skins.push(entry.file_name().to_string_lossy().to_string());
+			}
+		}
+	}
+	skins.sort();
+	skins.insert(0, DEFAULT_SKIN.to_string());
+	skins
+}
+
+impl<'a> Skin<'a> {
+	/// Load the bundled images from `res/images`, or their embedded copies
+	pub fn bundled(
+		res_dir: &Path,
+		texture_creator: &'a TextureCreator<WindowContext>,
+	) -> Result<Skin<'a>, String> {
+		let mut skin = Skin {
+			textures: Vec::new(),
+			tiles: Vec::new(),
+			walls: vec![None; WALL_VARIANTS],
+		};
+		for image in ROLES.iter() {
+			skin.textures
+				.push(load_bundled(texture_creator, res_dir, image)?);
+			skin.tiles.push((skin.textures.len() - 1, None));
+		}
+		Ok(skin)
+	}
+
+	/// Load a skin pack from `res/skins/<name>`, whose manifest maps roles to images:
+	/// `wall = wall.png`, or to `column,row` tiles of a `tilesheet = sheet.png` with
+	/// `tile = WxH` pixels; missing roles fall back to the bundled images.
+	/// Walls may have variants `wall_<mask>` for each mask of WALL_* neighbors
+	pub fn load(
+		res_dir: &Path,
+		texture_creator: &'a TextureCreator<WindowContext>,
+		name: &str,
+	) -> Result<Skin<'a>, String> {
+		let mut skin = Skin::bundled(res_dir, texture_creator)?;
+		if name == DEFAULT_SKIN {
+			return Ok(skin);
+		}
+		let dir = res_dir.join(SKINS_DIR).join(name);
+		let manifest_file = dir.join(MANIFEST_FILE);
+		let mut manifest = String::new();
+		File::open(&manifest_file)
+			.and_then(|mut f| f.read_to_string(&mut manifest))
+			.map_err(|e| format!("Couldn't read {}: {}", manifest_file.display(), e))?;
+		let mut entries = Vec::new();
+		let mut sheet = None;
+		let mut tile = None;
+		for line in manifest.lines() {
+			let line = line.trim();
+			if line.is_empty() || line.starts_with('#') {
+				continue;
+			}
+			let invalid = || format!("Invalid line [{}] in {}", line, manifest_file.display());
+			let i = line.find('=').ok_or_else(invalid)?;
+			let (name, value) = (line[..i].trim(), line[i + 1..].trim());
+			match name {
+				"tilesheet" => sheet = Some(load(texture_creator, &dir.join(value))?),
+				"tile" => tile = Some(pair(value, 'x').ok_or_else(invalid)?),
+				_ if name.starts_with(WALL_PREFIX) => {
+					match name[WALL_PREFIX.len()..].parse::<usize>() {
+						Ok(mask) if mask < WALL_VARIANTS => {
+							entries.push((ROLES.len() + mask, value.to_string()))
+						}
+						_ => return Err(invalid()),
+					}
+				}
+				_ => match ROLES.iter().position(|image| role_name(image) == name) {
+					Some(role) => entries.push((role, value.to_string())),
+					None => return Err(invalid()),
+				},
+			}
+		}
+		let sheet_index = sheet.map(|sheet| {
+			skin.textures.push(sheet);
+			skin.textures.len() - 1
+		});
+		for (role, value) in entries {
+			let tile = match (sheet_index, tile, pair(&value, ',')) {
+				(Some(index), Some((w, h)), Some((col, row))) => (
+					index,
+					Some(Rect::new((col * w) as i32, (row * h) as i32, w, h)),
+				),
+				(Some(_), _, _) => {
+					return Err(format!(
+						"Invalid tile [{}] in {}",
+						value,
+						manifest_file.display()
+					))
+				}
+				(None, _, _) => {
+					skin.textures
+						.push(load(texture_creator, &dir.join(&value))?);
+					(skin.textures.len() - 1, None)
+				}
+			};
+			if role < ROLES.len() {
+				skin.tiles[role] = tile;
+			} else {
+				skin.walls[role - ROLES.len()] = Some(tile);
+			}
+		}
+		Ok(skin)
+	}
+
+	/// Size of the tiles, in pixels
+	pub fn tile_size(&self) -> (u32, u32) {
+		let (texture, rect) = self.tiles[0];
+		match rect {
+			Some(rect) => (rect.width(), rect.height()),
+			None => {
+				let query = self.textures[texture].query();
+				(query.width, query.height)
+			}
+		}
+	}
+
+	fn draw_tile(&self, canvas: &mut Canvas<Window>, (texture, src): Tile, rect: Rect) {
+		canvas
+			.copy(&self.textures[texture], src, rect)
+			.expect("Couldn't copy texture into window");
+	}
+
+	pub fn draw(&self, canvas: &mut Canvas<Window>, role: usize, rect: Rect) {
+		self.draw_tile(canvas, self.tiles[role], rect);
+	}
+
+	/// Draw the tile of `role` tinted by `tint`, if any, eg: the colored crates and stores
+	pub fn draw_tinted(
+		&mut self,
+		canvas: &mut Canvas<Window>,
+		role: usize,
+		rect: Rect,
+		tint: Option<Color>,
+	) {
+		let tile = self.tiles[role];
+		let color = tint.unwrap_or(Color::RGB(255, 255, 255));
+		self.textures[tile.0].set_color_mod(color.r, color.g, color.b);
+		self.draw_tile(canvas, tile, rect);
+		self.textures[tile.0].set_color_mod(255, 255, 255);
+	}
+
+	/// Draw the wall variant matching its `mask` of wall neighbors; without variants,
+	/// shade the plain wall sides that don't join another wall
+	pub fn draw_wall(&self, canvas: &mut Canvas<Window>, mask: usize, rect: Rect) {
+		if let Some(tile) = self.walls[mask] {
+			self.draw_tile(canvas, tile, rect);
+			return;
+		}
+		self.draw(canvas, N_WALL, rect);
+		let t = ZOOM as u32;
+		let (x, y, w, h) = (rect.x(), rect.y(), rect.width(), rect.height());
+		canvas.set_draw_color(Color::RGB(48, 48, 48));
+		for &(bit, side) in [
+			(WALL_N, Rect::new(x, y, w, t)),
+			(WALL_E, Rect::new(x + (w - t) as i32, y, t, h)),
+			(WALL_S, Rect::new(x, y + (h - t) as i32, w, t)),
+			(WALL_W, Rect::new(x, y, t, h)),
+		]
+		.iter()
+		{
+			if mask & bit == 0 {
+				canvas.fill_rect(side).expect("Couldn't fill rect");
+			}
+		}
+	}
+}
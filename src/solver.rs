@@ -0,0 +1,352 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashSet, VecDeque};
+
+use crate::color;
+use crate::path::{self, DIRS};
+use crate::tile;
+use crate::topology::Topology;
+use crate::{Level, Map, CRATE, STORE, WALL};
+
+const MAX_STATES: usize = 500_000; // give up beyond that many positions
+const GREED: u32 = 3; // weight of the pushes estimate, trading shorter solutions for speed
+const LURD: [char; 4] = ['l', 'u', 'r', 'd']; // in DIRS order
+
+/// LURD letter of a unit move, uppercase for a push
+pub fn lurd(dx: isize, dy: isize, push: bool) -> char {
+	let d = DIRS
+		.iter()
+		.position(|&dir| dir == (dx, dy))
+		.expect("Not a unit move");
+	if push {
+		LURD[d].to_ascii_uppercase()
+	} else {
+		LURD[d]
+	}
+}
+
+/// Unit move of a LURD letter, whatever its case
+pub fn lurd_move(c: char) -> Option<(isize, isize)> {
+	LURD.iter()
+		.position(|&l| l == c.to_ascii_lowercase())
+		.map(|d| DIRS[d])
+}
+
+/// Item of an extended LURD solution
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Token {
+	Move(isize, isize),
+	Worker(usize), // make this worker, 0-based, the active one
+}
+
+/// Extended LURD token switching to `worker`: its 1-based number in brackets, eg: [2]
+pub fn worker_token(worker: usize) -> String {
+	format!("[{}]", worker + 1)
+}
+
+/// Worker active at the end of the extended LURD `lurd`, the first one until a switch
+pub fn last_worker(lurd: &str) -> usize {
+	lurd.rfind('[')
+		.and_then(|i| lurd[i + 1..].split(']').next())
+		.and_then(|n| n.parse::<usize>().ok())
+		.map_or(0, |n| n - 1)
+}
+
+/// Read an extended LURD solution: LURD letters, and worker switches; each token comes
+/// with its position, for the error messages
+pub fn tokens(text: &str) -> Result<Vec<(usize, Token)>, String> {
+	let chars: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+	let mut tokens = Vec::new();
+	let mut i = 0;
+	while i < chars.len() {
+		let c = chars[i];
+		if c == '[' {
+			let end = chars[i..].iter().position(|&c| c == ']');
+			let number: Option<usize> = end
+				.map(|end| chars[i + 1..i + end].iter().collect::<String>())
+				.and_then(|n| n.parse().ok());
+			match (end, number) {
+				(Some(end), Some(n)) if n > 0 => {
+					tokens.push((i + 1, Token::Worker(n - 1)));
+					i += end + 1;
+					continue;
+				}
+				_ => return Err(format!("Invalid worker switch at {}", i + 1)),
+			}
+		}
+		let (dx, dy) = lurd_move(c).ok_or_else(|| format!("Invalid move [{}] at {}", c, i + 1))?;
+		tokens.push((i + 1, Token::Move(dx, dy)));
+		i += 1;
+	}
+	Ok(tokens)
+}
+
+/// Fewest pushes bringing a crate from each cell to some store, None for dead cells
+fn distances(level: &Level) -> Vec<Vec<Option<u32>>> {
+	let map = &level.map;
+	let mut dist: Vec<Vec<Option<u32>>> = map.iter().map(|line| vec![None; line.len()]).collect();
+	let mut queue = VecDeque::new();
+	for (j, line) in map.iter().enumerate() {
+		for (i, &e) in line.iter().enumerate() {
+			if e & STORE == STORE {
+				dist[j][i] = Some(0);
+				queue.push_back((i, j));
+			}
+		}
+	}
+	let open = |c: Option<(usize, usize)>| c.filter(|&(x, y)| map[y][x] & WALL != WALL);
+	while let Some((x, y)) = queue.pop_front() {
+		let d = dist[y][x].unwrap_or(0);
+		for &(dx, dy) in DIRS.iter() {
+			// pull the crate back to p, the player stepping back beyond p
+			if let Some(p) = open(path::step(map, x, y, dx, dy)) {
+				if dist[p.1][p.0].is_none() && open(path::step(map, p.0, p.1, dx, dy)).is_some() {
+					dist[p.1][p.0] = Some(d + 1);
+					queue.push_back(p);
+				}
+			}
+		}
+	}
+	dist
+}
+
+/// Mark the cells from which a crate can still be pushed to some store
+pub fn live_cells(level: &Level) -> Vec<Vec<bool>> {
+	distances(level)
+		.iter()
+		.map(|line| line.iter().map(Option::is_some).collect())
+		.collect()
+}
+
+/// True if the crate at x:y is part of a 2x2 block of crates and walls, with a crate
+/// out of store: none of them can ever move again
+pub(crate) fn frozen(map: &Map, x: usize, y: usize) -> bool {
+	let blocked = |x: usize, y: usize| map[y][x] & (WALL | CRATE) != 0;
+	let unstored = |x: usize, y: usize| map[y][x] & (CRATE | STORE) == CRATE;
+	for &(dx, dy) in [(-1, -1), (1, -1), (-1, 1), (1, 1)].iter() {
+		let (nx, ny) = (x as isize + dx, y as isize + dy);
+		if nx < 0 || ny < 0 || ny as usize >= map.len() || nx as usize >= map[0].len() {
+			continue;
+		}
+		let (nx, ny) = (nx as usize, ny as usize);
+		let block = [(x, y), (nx, y), (x, ny), (nx, ny)];
+		if block.iter().all(|&(i, j)| blocked(i, j)) && block.iter().any(|&(i, j)| unstored(i, j)) {
+			return true;
+		}
+	}
+	false
+}
+
+/// Why `solve` found no solution
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Unsolved {
+	Unsupported, // not a square grid, several workers, special tiles or colored crates
+	NoSolution,  // every position was searched
+	GaveUp,      // too many positions to search
+}
+
+// A search node is a sorted set of crates and the player position, reached by a push
+struct Node {
+	crates: Vec<(usize, usize)>,
+	player: (usize, usize),
+	parent: usize,
+	push: Option<(usize, usize, usize)>, // crate position and DIRS index of the push
+}
+
+pub(crate) fn with_crates(base: &Map, crates: &[(usize, usize)]) -> Map {
+	let mut map = base.clone();
+	for &(x, y) in crates {
+		map[y][x] |= CRATE;
+	}
+	map
+}
+
+/// First reachable cell, identifying the area the player can walk in
+pub(crate) fn area(reach: &[Vec<bool>]) -> (usize, usize) {
+	for (j, line) in reach.iter().enumerate() {
+		if let Some(i) = line.iter().position(|&r| r) {
+			return (i, j);
+		}
+	}
+	(0, 0)
+}
+
+/// Walk and push along the chain of pushes leading to node `last`
+fn moves(level: &Level, nodes: &[Node], last: usize) -> String {
+	let mut chain = Vec::new();
+	let mut i = last;
+	while let Some(push) = nodes[i].push {
+		chain.push(push);
+		i = nodes[i].parent;
+	}
+	chain.reverse();
+	walk_and_push(level, &chain)
+}
+
+/// LURD moves of a chain of pushes, given as crate position and DIRS index, walking the
+/// shortest paths between them
+pub(crate) fn walk_and_push(level: &Level, chain: &[(usize, usize, usize)]) -> String {
+	let mut map = level.map.clone();
+	let mut p = (level.px, level.py);
+	let mut lurds = String::new();
+	for &(cx, cy, d) in chain {
+		let (dx, dy) = DIRS[d];
+		let side = ((cx as isize - dx) as usize, (cy as isize - dy) as usize);
+		for (mx, my) in
+			path::find_path(Topology::Square, &map, p, side).expect("Lost the player path")
+		{
+			lurds.push(lurd(mx, my, false));
+		}
+		lurds.push(lurd(dx, dy, true));
+		map[cy][cx] &= !CRATE;
+		map[(cy as isize + dy) as usize][(cx as isize + dx) as usize] |= CRATE;
+		p = (cx, cy);
+	}
+	lurds
+}
+
+/// Find a solution, searching first the positions whose crates are the closest to the
+/// stores, as LURD moves; LURD moves only exist on square grids, and the search moves a
+/// single worker over classic cells and plain crates
+pub fn solve(level: &Level) -> Result<String, Unsolved> {
+	if level.topology != Topology::Square
+		|| level.workers.len() > 1
+		|| tile::any(&level.tiles)
+		|| color::any(level)
+	{
+		return Err(Unsolved::Unsupported);
+	}
+	let dist = distances(level);
+	let mut base = level.map.clone();
+	let mut crates = Vec::new();
+	for (j, line) in base.iter_mut().enumerate() {
+		for (i, e) in line.iter_mut().enumerate() {
+			if *e & CRATE == CRATE {
+				*e &= !CRATE;
+				crates.push((i, j));
+			}
+		}
+	}
+	// pushes estimate of the remaining crates, None if one of them is stuck
+	let estimate = |crates: &[(usize, usize)]| -> Option<u32> {
+		crates.iter().map(|&(x, y)| dist[y][x]).sum()
+	};
+	let h = estimate(&crates).ok_or(Unsolved::NoSolution)?;
+	crates.sort_unstable();
+	let player = (level.px, level.py);
+	let mut seen = HashSet::new();
+	let start = area(&path::reachable(
+		Topology::Square,
+		&with_crates(&base, &crates),
+		player,
+	));
+	seen.insert((crates.clone(), start));
+	let mut nodes = vec![Node {
+		crates,
+		player,
+		parent: 0,
+		push: None,
+	}];
+	let mut queue = BinaryHeap::new();
+	queue.push(Reverse((GREED * h, 0, 0)));
+	while let Some(Reverse((_, g, i))) = queue.pop() {
+		if nodes.len() >= MAX_STATES {
+			return Err(Unsolved::GaveUp);
+		}
+		let current = nodes[i].crates.clone();
+		if current.iter().all(|&(x, y)| base[y][x] & STORE == STORE) {
+			return Ok(moves(level, &nodes, i));
+		}
+		let map = with_crates(&base, &current);
+		let reach = path::reachable(Topology::Square, &map, nodes[i].player);
+		for (k, &(cx, cy)) in current.iter().enumerate() {
+			for (d, &(dx, dy)) in DIRS.iter().enumerate() {
+				match path::step(&map, cx, cy, -dx, -dy) {
+					Some((sx, sy)) if reach[sy][sx] => {}
+					_ => continue,
+				}
+				let to = match path::step(&map, cx, cy, dx, dy) {
+					Some((tx, ty)) if map[ty][tx] & (WALL | CRATE) == 0 => (tx, ty),
+					_ => continue,
+				};
+				let mut after = map.clone();
+				after[cy][cx] &= !CRATE;
+				after[to.1][to.0] |= CRATE;
+				if frozen(&after, to.0, to.1) {
+					continue;
+				}
+				let mut crates = current.clone();
+				crates[k] = to;
+				let h = match estimate(&crates) {
+					Some(h) => h,
+					None => continue,
+				};
+				crates.sort_unstable();
+				let key = (
+					crates,
+					area(&path::reachable(Topology::Square, &after, (cx, cy))),
+				);
+				if seen.contains(&key) {
+					continue;
+				}
+				seen.insert((key.0.clone(), key.1));
+				nodes.push(Node {
+					crates: key.0,
+					player: (cx, cy),
+					parent: i,
+					push: Some((cx, cy, d)),
+				});
+				queue.push(Reverse((g + 1 + GREED * h, g + 1, nodes.len() - 1)));
+			}
+		}
+	}
+	Err(Unsolved::NoSolution)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::valid_levels;
+
+	#[test]
+	fn solve_tells_why_it_failed() {
+		let levels = valid_levels(concat!(
+			"######\n#@$ .#\n######\n\n",
+			"#####\n#@ .#\n#$  #\n#####\n\n",
+			"#####\n#@$.#\n#####\n; Topology: hex\n",
+		));
+		assert_eq!(solve(&levels[0]), Ok("RR".to_string()));
+		assert_eq!(solve(&levels[1]), Err(Unsolved::NoSolution));
+		assert_eq!(solve(&levels[2]), Err(Unsolved::Unsupported));
+	}
+
+	#[test]
+	fn tokens_read_moves_and_worker_switches() {
+		let read: Vec<Token> = tokens("R [2]u\nL[10]")
+			.unwrap()
+			.into_iter()
+			.map(|(_, token)| token)
+			.collect();
+		assert_eq!(
+			read,
+			vec![
+				Token::Move(1, 0),
+				Token::Worker(1),
+				Token::Move(0, -1),
+				Token::Move(-1, 0),
+				Token::Worker(9),
+			]
+		);
+		assert_eq!(tokens("rx"), Err("Invalid move [x] at 2".to_string()));
+		assert!(tokens("r[0]").is_err());
+		assert!(tokens("r[2").is_err());
+	}
+
+	#[test]
+	fn last_worker_follows_the_switches() {
+		assert_eq!(last_worker(""), 0);
+		assert_eq!(last_worker("rRu"), 0);
+		assert_eq!(last_worker("R[2]uL"), 1);
+		assert_eq!(last_worker("R[12]uL[3]"), 2);
+		assert_eq!(worker_token(2), "[3]");
+	}
+}
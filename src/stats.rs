@@ -0,0 +1,285 @@
+use std::fs::{create_dir_all, read_to_string, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{data_file, hms};
+
+pub const STATS_FILE: &str = "stats.csv";
+const HEADER: &str = "date,level,outcome,moves,pushes,undos,time_ms";
+
+/// How an attempt at a level ended
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Outcome {
+	Won,
+	Restarted,
+	Left, // for another level, or quitting
+}
+
+const OUTCOMES: [(Outcome, &str); 3] = [
+	(Outcome::Won, "won"),
+	(Outcome::Restarted, "restarted"),
+	(Outcome::Left, "left"),
+];
+
+/// One attempt at a level, as a line of the history
+#[derive(Clone, Debug)]
+pub struct Attempt {
+	pub date: String, // UTC, eg: 2024-01-31T23:59:59Z
+	pub level: usize, // 0-based, 1-based in the file
+	pub outcome: Outcome,
+	pub moves: i32,
+	pub pushes: i32,
+	pub undos: u32,
+	pub time_ms: u64,
+}
+
+/// Totals of the attempts at one level
+#[derive(Default, Debug)]
+pub struct LevelStats {
+	pub attempts: u32,
+	pub restarts: u32,
+	pub undos: u32,
+	pub time_ms: u64,
+	pub best_moves: Option<i32>,
+	pub best_pushes: Option<i32>,
+	pub completed: Option<String>, // date of the first win
+}
+
+/// History file kept along with `scores_file`
+pub fn stats_file(scores_file: &Path) -> PathBuf {
+	data_file(scores_file, STATS_FILE)
+}
+
+/// Current UTC date and time
+pub fn now() -> String {
+	let secs = SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.map_or(0, |d| d.as_secs());
+	let (days, secs) = (secs / 86400, secs % 86400);
+	// civil from days, see http://howardhinnant.github.io/date_algorithms.html
+	let z = days + 719_468;
+	let era = z / 146_097;
+	let doe = z % 146_097;
+	let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+	let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+	let mp = (5 * doy + 2) / 153;
+	let d = doy - (153 * mp + 2) / 5 + 1;
+	let m = if mp < 10 { mp + 3 } else { mp - 9 };
+	let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+	format!(
+		"{}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+		y,
+		m,
+		d,
+		secs / 3600,
+		secs / 60 % 60,
+		secs % 60
+	)
+}
+
+fn attempt_line(a: &Attempt) -> String {
+	let outcome = OUTCOMES.iter().find(|o| o.0 == a.outcome).unwrap().1;
+	format!(
+		"{},{},{},{},{},{},{}",
+		a.date,
+		a.level + 1,
+		outcome,
+		a.moves,
+		a.pushes,
+		a.undos,
+		a.time_ms
+	)
+}
+
+fn parse_attempt(line: &str) -> Option<Attempt> {
+	let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+	match fields.as_slice() {
+		[date, level, outcome, moves, pushes, undos, time_ms] => Some(Attempt {
+			date: date.to_string(),
+			level: level.parse::<usize>().ok()?.checked_sub(1)?,
+			outcome: OUTCOMES.iter().find(|o| o.1 == *outcome)?.0,
+			moves: moves.parse().ok()?,
+			pushes: pushes.parse().ok()?,
+			undos: undos.parse().ok()?,
+			time_ms: time_ms.parse().ok()?,
+		}),
+		_ => None,
+	}
+}
+
+/// Load the history; a missing file gives an empty one
+pub fn load(file: &Path) -> Vec<Attempt> {
+	let text = read_to_string(file).unwrap_or_default();
+	text.lines()
+		.filter(|line| !line.is_empty() && *line != HEADER)
+		.map(|line| {
+			parse_attempt(line).unwrap_or_else(|| {
+				panic!(
+					"Invalid line [{}]. Please delete the stats file {}.",
+					line,
+					file.display()
+				)
+			})
+		})
+		.collect()
+}
+
+/// Add an attempt at the end of the history file, creating it if needed
+pub fn append(file: &Path, attempt: &Attempt) {
+	if let Some(dir) = file.parent() {
+		create_dir_all(dir).unwrap();
+	}
+	let new = !file.is_file();
+	let mut f = OpenOptions::new()
+		.create(true)
+		.append(true)
+		.open(file)
+		.unwrap();
+	if new {
+		writeln!(f, "{}", HEADER).unwrap();
+	}
+	writeln!(f, "{}", attempt_line(attempt)).unwrap();
+}
+
+/// History as CSV, with a header line
+pub fn csv(history: &[Attempt]) -> String {
+	let mut text = format!("{}\n", HEADER);
+	for attempt in history {
+		text.push_str(&attempt_line(attempt));
+		text.push('\n');
+	}
+	text
+}
+
+/// Totals of the history for each of the `levels` levels
+pub fn per_level(history: &[Attempt], levels: usize) -> Vec<LevelStats> {
+	let mut stats: Vec<LevelStats> = (0..levels).map(|_| Default::default()).collect();
+	for a in history.iter().filter(|a| a.level < levels) {
+		let s = &mut stats[a.level];
+		s.attempts += 1;
+		s.undos += a.undos;
+		s.time_ms += a.time_ms;
+		match a.outcome {
+			Outcome::Won => {
+				s.best_moves = Some(s.best_moves.map_or(a.moves, |m| m.min(a.moves)));
+				s.best_pushes = Some(s.best_pushes.map_or(a.pushes, |p| p.min(a.pushes)));
+				if s.completed.is_none() {
+					s.completed = Some(a.date.clone());
+				}
+			}
+			Outcome::Restarted => s.restarts += 1,
+			Outcome::Left => {}
+		}
+	}
+	stats
+}
+
+/// Progress over the collection of `levels` levels, then one line per level played
+pub fn summary(history: &[Attempt], levels: usize) -> Vec<String> {
+	let stats = per_level(history, levels);
+	let solved = stats.iter().filter(|s| s.completed.is_some()).count();
+	let attempts: u32 = stats.iter().map(|s| s.attempts).sum();
+	let restarts: u32 = stats.iter().map(|s| s.restarts).sum();
+	let undos: u32 = stats.iter().map(|s| s.undos).sum();
+	let time_ms: u64 = stats.iter().map(|s| s.time_ms).sum();
+	let mut lines = vec![
+		format!(
+			"Solved {}/{} levels: {} attempts, {} restarts, {} undos, {} played",
+			solved,
+			levels,
+			attempts,
+			restarts,
+			undos,
+			hms(time_ms)
+		),
+		"Level Attempts Restarts Undos     Time Moves Pushes Completed".to_string(),
+	];
+	let best = |n: Option<i32>| n.map_or("-".to_string(), |n| n.to_string());
+	for (i, s) in stats.iter().enumerate().filter(|(_, s)| s.attempts > 0) {
+		lines.push(format!(
+			"{:>5} {:>8} {:>8} {:>5} {:>8} {:>5} {:>6} {}",
+			i + 1,
+			s.attempts,
+			s.restarts,
+			s.undos,
+			hms(s.time_ms),
+			best(s.best_moves),
+			best(s.best_pushes),
+			s.completed
+				.as_ref()
+				.map_or("-", |d| d.get(..10).unwrap_or(d)),
+		));
+	}
+	lines
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn attempt(date: &str, level: usize, outcome: Outcome, moves: i32) -> Attempt {
+		Attempt {
+			date: date.to_string(),
+			level,
+			outcome,
+			moves,
+			pushes: moves / 2,
+			undos: 1,
+			time_ms: 60_000,
+		}
+	}
+
+	#[test]
+	fn attempt_lines_parse_back() {
+		let a = parse_attempt("2024-01-31T23:59:59Z, 3, won, 10, 4, 2, 5000").unwrap();
+		assert_eq!(a.level, 2);
+		assert_eq!(a.outcome, Outcome::Won);
+		assert_eq!(attempt_line(&a), "2024-01-31T23:59:59Z,3,won,10,4,2,5000");
+		assert!(parse_attempt("2024-01-31T23:59:59Z,0,won,10,4,2,5000").is_none());
+		assert!(parse_attempt("2024-01-31T23:59:59Z,3,lost,10,4,2,5000").is_none());
+		assert!(parse_attempt("2024-01-31T23:59:59Z,3,won,10,4,2").is_none());
+	}
+
+	#[test]
+	fn csv_parses_back() {
+		let history = vec![
+			attempt("2024-01-01T00:00:00Z", 0, Outcome::Restarted, 7),
+			attempt("2024-01-02T00:00:00Z", 1, Outcome::Left, 3),
+		];
+		let text = csv(&history);
+		let lines: Vec<&str> = text.lines().collect();
+		assert_eq!(lines[0], HEADER);
+		let parsed: Vec<Attempt> = lines[1..]
+			.iter()
+			.map(|line| parse_attempt(line).unwrap())
+			.collect();
+		assert_eq!(csv(&parsed), text);
+	}
+
+	#[test]
+	fn per_level_adds_up_the_attempts() {
+		let history = vec![
+			attempt("2024-01-01T00:00:00Z", 0, Outcome::Won, 12),
+			attempt("2024-01-02T00:00:00Z", 0, Outcome::Restarted, 3),
+			attempt("2024-01-03T00:00:00Z", 0, Outcome::Won, 10),
+			attempt("2024-01-04T00:00:00Z", 5, Outcome::Won, 8),
+		];
+		let stats = per_level(&history, 2);
+		assert_eq!(stats[0].attempts, 3);
+		assert_eq!(stats[0].restarts, 1);
+		assert_eq!(stats[0].undos, 3);
+		assert_eq!(stats[0].time_ms, 180_000);
+		assert_eq!(stats[0].best_moves, Some(10));
+		assert_eq!(stats[0].best_pushes, Some(5));
+		assert_eq!(stats[0].completed.as_deref(), Some("2024-01-01T00:00:00Z"));
+		assert_eq!(stats[1].attempts, 0);
+		assert_eq!(stats[1].completed, None);
+		let lines = summary(&history, 2);
+		assert_eq!(lines.len(), 3);
+		assert_eq!(
+			lines[0],
+			"Solved 1/2 levels: 3 attempts, 1 restarts, 3 undos, 0:03:00 played"
+		);
+	}
+}
@@ -0,0 +1,73 @@
+/// Special floor of a cell, beyond the classic elements; tiles don't move, and hold
+/// nothing at the start
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum Tile {
+	#[default]
+	Floor,
+	OneWay(isize, isize), // only entered moving this way
+	Ice,                  // crates slide over it until blocked
+	Teleporter(u8),       // pair number: what enters one comes out of the other, if free
+}
+
+pub type Tiles = Vec<Vec<Tile>>;
+
+/// Tile of each character but the teleporters, numbered by digits
+const TILES: [(char, Tile); 5] = [
+	('<', Tile::OneWay(-1, 0)),
+	('^', Tile::OneWay(0, -1)),
+	('>', Tile::OneWay(1, 0)),
+	('v', Tile::OneWay(0, 1)),
+	('~', Tile::Ice),
+];
+
+/// Tile of the character `c`, if special
+pub fn parse(c: char) -> Option<Tile> {
+	match c.to_digit(10) {
+		Some(n) => Some(Tile::Teleporter(n as u8)),
+		None => TILES.iter().find(|t| t.0 == c).map(|t| t.1),
+	}
+}
+
+/// Character of the special tile `tile`
+pub fn to_char(tile: Tile) -> char {
+	match tile {
+		Tile::Teleporter(n) => std::char::from_digit(n as u32, 10).unwrap(),
+		_ => TILES.iter().find(|t| t.1 == tile).map_or(' ', |t| t.0),
+	}
+}
+
+/// Whether some cell is special
+pub fn any(tiles: &[Vec<Tile>]) -> bool {
+	tiles.iter().flatten().any(|&t| t != Tile::Floor)
+}
+
+/// Other teleporter of the pair at x:y, if x:y is a teleporter
+pub fn twin(tiles: &[Vec<Tile>], x: usize, y: usize) -> Option<(usize, usize)> {
+	let tile = tiles[y][x];
+	if !matches!(tile, Tile::Teleporter(_)) {
+		return None;
+	}
+	for (j, line) in tiles.iter().enumerate() {
+		for (i, &t) in line.iter().enumerate() {
+			if t == tile && (i, j) != (x, y) {
+				return Some((i, j));
+			}
+		}
+	}
+	None
+}
+
+/// Check that each teleporter has a twin
+pub fn check(tiles: &[Vec<Tile>]) -> Result<(), String> {
+	for n in 0..10 {
+		let count = tiles
+			.iter()
+			.flatten()
+			.filter(|&&t| t == Tile::Teleporter(n))
+			.count();
+		if count != 0 && count != 2 {
+			return Err(format!("Teleporter {} found {} times", n, count));
+		}
+	}
+	Ok(())
+}
@@ -0,0 +1,322 @@
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::exit;
+
+use cratesan::color;
+use cratesan::optimizer::{optimize, Metrics};
+use cratesan::solver::{self, lurd, tokens, Token, Unsolved};
+use cratesan::stats::{self, stats_file};
+use cratesan::tile;
+use cratesan::topology::Topology;
+use cratesan::{level_text, parse_levels, Engine, Level, Status, CRATE, EMPTY, WALL};
+
+use crate::cli::{Options, Tool};
+
+fn read_file(file: &Path) -> String {
+	let mut text = String::new();
+	File::open(file)
+		.and_then(|mut f| f.read_to_string(&mut text))
+		.unwrap_or_else(|e| panic!("Couldn't read {}: {}", file.display(), e));
+	text
+}
+
+fn check_level(levels: &[Level], level: usize) {
+	if level >= levels.len() {
+		eprintln!("No level {}, there are {} levels", level + 1, levels.len());
+		exit(2);
+	}
+}
+
+/// Whether `level`, number `i`, can have LURD moves, which only exist on square grids
+fn lurd_grid(level: &Level, i: usize) -> bool {
+	if level.topology != Topology::Square {
+		println!(
+			"Level {}: LURD moves need a square grid, not a {} one",
+			i + 1,
+			level.topology.name()
+		);
+		return false;
+	}
+	true
+}
+
+/// Whether `level`, number `i`, has a single worker and no special tiles, as the solver
+/// and the optimizer need
+fn searchable(level: &Level, i: usize) -> bool {
+	let issue = if level.workers.len() > 1 {
+		format!("{} workers", level.workers.len())
+	} else if tile::any(&level.tiles) {
+		"special tiles".to_string()
+	} else if color::any(level) {
+		"colored crates".to_string()
+	} else {
+		return true;
+	};
+	println!("Level {}: {}, only verify takes them", i + 1, issue);
+	false
+}
+
+fn solve(levels: &[Level], level: Option<usize>) -> bool {
+	let mut all_solved = true;
+	for (i, lev) in levels.iter().enumerate() {
+		if matches!(level, Some(n) if n != i) {
+			continue;
+		}
+		if !lurd_grid(lev, i) || !searchable(lev, i) {
+			all_solved = false;
+			continue;
+		}
+		match solver::solve(lev) {
+			Ok(lurd) => {
+				let metrics = Metrics::of(&lurd);
+				println!(
+					"Level {}: {} ({} moves, {} pushes)",
+					i + 1,
+					lurd,
+					metrics.moves,
+					metrics.pushes
+				);
+			}
+			Err(unsolved) => {
+				let why = match unsolved {
+					Unsolved::Unsupported => "not searchable",
+					Unsolved::NoSolution => "no solution",
+					Unsolved::GaveUp => "search gave up, too many positions",
+				};
+				println!("Level {}: {}", i + 1, why);
+				all_solved = false;
+			}
+		}
+	}
+	all_solved
+}
+
+/// LURD solution of `level` given inline or as a file, possibly in the solutions file
+/// format, eg: `Level 3: ulDR (4 moves, 2 pushes)`, taking the last line of that level
+fn solution_text(solution: &str, level: usize) -> Result<String, String> {
+	let text = if Path::new(solution).is_file() {
+		read_file(Path::new(solution))
+	} else {
+		solution.to_string()
+	};
+	if !text.trim_start().starts_with("Level") {
+		return Ok(text);
+	}
+	text.lines()
+		.rev()
+		.filter_map(|line| {
+			let (number, lurd) = line.trim().strip_prefix("Level")?.split_once(':')?;
+			Some((number.trim().parse::<usize>().ok()?, lurd))
+		})
+		.find(|&(number, _)| number == level + 1)
+		.map(|(_, lurd)| lurd.split('(').next().unwrap_or_default().to_string())
+		.ok_or(format!(
+			"No solution of level {} in {}",
+			level + 1,
+			solution
+		))
+}
+
+/// Replay `solution` through the engine, as the player would
+fn verify(levels: Vec<Level>, level: usize, solution: &str) -> bool {
+	if !lurd_grid(&levels[level], level) {
+		return false;
+	}
+	let tokens = match solution_text(solution, level).and_then(|solution| tokens(&solution)) {
+		Ok(tokens) => tokens,
+		Err(e) => {
+			println!("{}", e);
+			return false;
+		}
+	};
+	let mut engine = Engine::offline(levels);
+	engine.set_level(level);
+	for (i, token) in tokens {
+		match token {
+			Token::Worker(worker) if worker >= engine.snap.state.workers.len() => {
+				println!("No worker {} at {}", worker + 1, i);
+				return false;
+			}
+			Token::Worker(worker) => {
+				engine.switch_worker(worker);
+			}
+			Token::Move(_, _) if matches!(engine.status, Status::Win) => {
+				println!("Moves after the win at {}", i);
+				return false;
+			}
+			Token::Move(dx, dy) => {
+				if engine.try_move(dx, dy).is_none() {
+					println!("Blocked move [{}] at {}", lurd(dx, dy, false), i);
+					return false;
+				}
+			}
+		}
+	}
+	let state = &engine.snap.state;
+	if let Status::Win = engine.status {
+		if state.stored == engine.levels[level].crates {
+			println!(
+				"Level {} solved in {} moves, {} pushes",
+				level + 1,
+				state.moves,
+				state.pushes
+			);
+			return true;
+		}
+	}
+	println!(
+		"Level {} not solved: {}/{} crates stored",
+		level + 1,
+		state.stored,
+		engine.levels[level].crates
+	);
+	false
+}
+
+fn optimize_solution(levels: &[Level], level: usize, solution: &str) -> bool {
+	if !lurd_grid(&levels[level], level) || !searchable(&levels[level], level) {
+		return false;
+	}
+	let solution = match solution_text(solution, level) {
+		Ok(solution) => solution,
+		Err(e) => {
+			println!("{}", e);
+			return false;
+		}
+	};
+	match optimize(&levels[level], &solution) {
+		Ok(lurd) => {
+			let (before, after) = (Metrics::of(&solution), Metrics::of(&lurd));
+			println!(
+				"Level {}: {} moves, {} pushes -> {} moves, {} pushes",
+				level + 1,
+				before.moves,
+				before.pushes,
+				after.moves,
+				after.pushes
+			);
+			println!("{}", lurd);
+			true
+		}
+		Err(e) => {
+			println!("Level {}: {}", level + 1, e);
+			false
+		}
+	}
+}
+
+/// Issues found in a valid level
+fn lint_level(level: &Level) -> Vec<String> {
+	let mut issues = Vec::new();
+	let (w, h) = (level.w, level.h);
+	let open = (0..h).any(|j| {
+		(0..w).any(|i| level.inside[j][i] && (i == 0 || j == 0 || i == w - 1 || j == h - 1))
+	});
+	if open {
+		issues.push("not closed by walls".to_string());
+	}
+	if level.crates == 0 {
+		issues.push("no crates".to_string());
+	} else if level.stored == level.crates {
+		issues.push("already solved".to_string());
+	}
+	// dead cells are only known on square grids of classic cells
+	let live = match level.topology {
+		Topology::Square if !tile::any(&level.tiles) => solver::live_cells(level),
+		_ => level.inside.clone(),
+	};
+	for (j, line) in level.map.iter().enumerate() {
+		for (i, &e) in line.iter().enumerate() {
+			if e & CRATE == CRATE && !live[j][i] {
+				issues.push(format!(
+					"crate at {}:{} can't reach any store",
+					i + 1,
+					j + 1
+				));
+			} else if e != EMPTY && e != WALL && !level.inside[j][i] {
+				issues.push(format!("element outside the walls at {}:{}", i + 1, j + 1));
+			}
+		}
+	}
+	issues.extend(color::mismatches(level));
+	issues
+}
+
+fn lint(levels_text: &str) -> bool {
+	let mut ok = true;
+	for (i, level) in parse_levels(levels_text).iter().enumerate() {
+		let issues = match level {
+			Ok(level) => lint_level(level),
+			Err(e) => vec![e.clone()],
+		};
+		for issue in issues {
+			println!("Level {}: {}", i + 1, issue);
+			ok = false;
+		}
+	}
+	ok
+}
+
+fn write_out(text: &str, out: Option<&Path>) {
+	match out {
+		Some(out) => File::create(out)
+			.and_then(|mut f| f.write_all(text.as_bytes()))
+			.unwrap_or_else(|e| panic!("Couldn't write {}: {}", out.display(), e)),
+		None => print!("{}", text),
+	}
+}
+
+fn convert(levels: &[Level], xsb: bool, out: Option<&Path>) {
+	let text = levels
+		.iter()
+		.map(|level| level_text(level, xsb))
+		.collect::<Vec<_>>()
+		.join("\n");
+	write_out(&text, out);
+}
+
+/// Summarize the history of the attempts over the levels, or export it as CSV
+fn show_stats(options: &Options, csv: bool, out: Option<&Path>) {
+	let history = stats::load(&stats_file(&options.scores_file));
+	if csv {
+		write_out(&stats::csv(&history), out);
+	} else {
+		for line in stats::summary(&history, options.levels().len()) {
+			println!("{}", line);
+		}
+	}
+}
+
+/// Run a non-interactive tool, and exit with its status
+pub fn run(tool: &Tool, options: &Options) -> ! {
+	let ok = match tool {
+		Tool::Lint => lint(&options.levels_text()),
+		Tool::Convert(xsb, out) => {
+			convert(&options.levels(), *xsb, out.as_deref());
+			true
+		}
+		Tool::Stats(csv, out) => {
+			show_stats(options, *csv, out.as_deref());
+			true
+		}
+		Tool::Solve(level) => {
+			let levels = options.levels();
+			if let Some(level) = level {
+				check_level(&levels, *level);
+			}
+			solve(&levels, *level)
+		}
+		Tool::Verify(level, solution) => {
+			let levels = options.levels();
+			check_level(&levels, *level);
+			verify(levels, *level, solution)
+		}
+		Tool::Optimize(level, solution) => {
+			let levels = options.levels();
+			check_level(&levels, *level);
+			optimize_solution(&levels, *level, solution)
+		}
+	};
+	exit(if ok { 0 } else { 1 });
+}
@@ -0,0 +1,193 @@
+use crate::path;
+use crate::Map;
+
+/// Shape of the cells of a level, which sets how the moves connect them
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum Topology {
+	#[default]
+	Square,
+	Hex,      // pointy-top hexagons, the odd rows shifted right by half a cell
+	Triangle, // the triangle x:y points up when x+y is even, rows sharing their edges
+}
+
+const NAMES: [(Topology, &str); 3] = [
+	(Topology::Square, "square"),
+	(Topology::Hex, "hex"),
+	(Topology::Triangle, "triangle"),
+];
+
+/// Moves on a square grid: W N E S
+const SQUARE_MOVES: [(isize, isize); 4] = path::DIRS;
+/// Moves on hex and triangle grids: W NW NE E SE SW
+const SLANTED_MOVES: [(isize, isize); 6] = [(-1, 0), (-1, -1), (1, -1), (1, 0), (1, 1), (-1, 1)];
+
+/// Corners of each cell shape in its box, one cell wide and high
+const SQUARE: [(f32, f32); 4] = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+const HEX: [(f32, f32); 6] = [
+	(0.5, 0.0),
+	(1.0, 0.25),
+	(1.0, 0.75),
+	(0.5, 1.0),
+	(0.0, 0.75),
+	(0.0, 0.25),
+];
+const UP: [(f32, f32); 3] = [(0.5, 0.0), (1.0, 1.0), (0.0, 1.0)];
+const DOWN: [(f32, f32); 3] = [(0.0, 0.0), (1.0, 0.0), (0.5, 1.0)];
+
+impl Topology {
+	pub fn name(self) -> &'static str {
+		NAMES.iter().find(|(t, _)| *t == self).unwrap().1
+	}
+
+	/// Topology named `name`, eg: "hex"
+	pub fn parse(name: &str) -> Option<Topology> {
+		NAMES
+			.iter()
+			.find(|(_, n)| n.eq_ignore_ascii_case(name))
+			.map(|(t, _)| *t)
+	}
+
+	/// Moves the player can make, as screen directions dx:dy
+	pub fn moves(self) -> &'static [(isize, isize)] {
+		match self {
+			Topology::Square => &SQUARE_MOVES,
+			Topology::Hex | Topology::Triangle => &SLANTED_MOVES,
+		}
+	}
+
+	/// Whether the triangle x:y points up
+	pub fn points_up(x: usize, y: usize) -> bool {
+		(x + y).is_multiple_of(2)
+	}
+
+	/// Cell reached from x:y by the move dx:dy, None if the topology has no such move or
+	/// if it leaves the map; a crate pushed twice the same way goes straight
+	pub fn step(
+		self,
+		map: &Map,
+		x: usize,
+		y: usize,
+		dx: isize,
+		dy: isize,
+	) -> Option<(usize, usize)> {
+		let (dx, dy) = match (self, dx, dy) {
+			(_, 0, 0) => return None,
+			(Topology::Square, _, _) if dx.abs() + dy.abs() != 1 => return None,
+			(Topology::Square, _, _) => (dx, dy),
+			(_, _, 0) => (dx, 0),
+			(_, 0, _) => return None,
+			// between rows, the diagonal neighbours depend on the row offset
+			(Topology::Hex, _, _) if y.is_multiple_of(2) => (dx.min(0), dy),
+			(Topology::Hex, _, _) => (dx.max(0), dy),
+			// a triangle crosses its flat edge up or down, else a slanted one
+			(Topology::Triangle, _, 1) if Topology::points_up(x, y) => (0, 1),
+			(Topology::Triangle, _, -1) if !Topology::points_up(x, y) => (0, -1),
+			(Topology::Triangle, _, _) => (dx, 0),
+		};
+		path::step(map, x, y, dx, dy)
+	}
+
+	/// Size of a `w`x`h` map, in cells
+	pub fn extent(self, w: usize, h: usize) -> (f32, f32) {
+		match self {
+			Topology::Square => (w as f32, h as f32),
+			// rows overlap by a quarter of a hexagon
+			Topology::Hex => (w as f32 + 0.5, 0.75 * h.saturating_sub(1) as f32 + 1.0),
+			// neighbours overlap by half a triangle
+			Topology::Triangle => ((w + 1) as f32 / 2.0, h as f32),
+		}
+	}
+
+	/// Top left corner of the box of the cell x:y, in cells
+	pub fn origin(self, x: usize, y: usize) -> (f32, f32) {
+		let (fx, fy) = (x as f32, y as f32);
+		match self {
+			Topology::Square => (fx, fy),
+			Topology::Hex => (fx + 0.5 * (y % 2) as f32, 0.75 * fy),
+			Topology::Triangle => (0.5 * fx, fy),
+		}
+	}
+
+	/// Corners of the cell x:y in its box
+	pub fn outline(self, x: usize, y: usize) -> &'static [(f32, f32)] {
+		match self {
+			Topology::Square => &SQUARE,
+			Topology::Hex => &HEX,
+			Topology::Triangle if Topology::points_up(x, y) => &UP,
+			Topology::Triangle => &DOWN,
+		}
+	}
+
+	/// Cell of a `w`x`h` map at the point px:py, in cells from the map top left corner
+	pub fn cell_at(self, w: usize, h: usize, px: f32, py: f32) -> Option<(usize, usize)> {
+		(0..h)
+			.flat_map(|y| (0..w).map(move |x| (x, y)))
+			.find(|&(x, y)| {
+				let (ox, oy) = self.origin(x, y);
+				let corners = self.outline(x, y);
+				// inside a convex shape, the point is on the same side of all its edges
+				let sides: Vec<f32> = corners
+					.iter()
+					.zip(corners.iter().cycle().skip(1))
+					.map(|(a, b)| (b.0 - a.0) * (py - oy - a.1) - (b.1 - a.1) * (px - ox - a.0))
+					.collect();
+				sides.iter().all(|&s| s >= 0.0) || sides.iter().all(|&s| s <= 0.0)
+			})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn names_parse_back() {
+		for &(topology, name) in &NAMES {
+			assert_eq!(Topology::parse(name), Some(topology));
+			assert_eq!(topology.name(), name);
+		}
+		assert_eq!(Topology::parse("HEX"), Some(Topology::Hex));
+		assert_eq!(Topology::parse("cube"), None);
+	}
+
+	#[test]
+	fn hex_rows_shift_the_diagonals() {
+		let map = vec![vec![0; 4]; 4];
+		let hex = Topology::Hex;
+		assert_eq!(hex.step(&map, 2, 2, 1, -1), Some((2, 1)));
+		assert_eq!(hex.step(&map, 2, 2, -1, -1), Some((1, 1)));
+		assert_eq!(hex.step(&map, 2, 1, 1, -1), Some((3, 0)));
+		assert_eq!(hex.step(&map, 2, 1, -1, 1), Some((2, 2)));
+		assert_eq!(hex.step(&map, 2, 1, 0, 1), None);
+		assert_eq!(hex.step(&map, 0, 2, -1, 1), None);
+		assert_eq!(Topology::Square.step(&map, 2, 2, 1, 1), None);
+	}
+
+	#[test]
+	fn triangles_cross_their_flat_edge() {
+		let map = vec![vec![0; 5]; 4];
+		let triangle = Topology::Triangle;
+		// 2:2 points up, its flat edge below
+		assert_eq!(triangle.step(&map, 2, 2, 1, 1), Some((2, 3)));
+		assert_eq!(triangle.step(&map, 2, 2, 1, -1), Some((3, 2)));
+		assert_eq!(triangle.step(&map, 2, 2, -1, -1), Some((1, 2)));
+		// 3:2 points down, its flat edge above
+		assert_eq!(triangle.step(&map, 3, 2, -1, -1), Some((3, 1)));
+		assert_eq!(triangle.step(&map, 3, 2, 1, 1), Some((4, 2)));
+	}
+
+	#[test]
+	fn cell_at_finds_the_cell_around_its_center() {
+		for &topology in &[Topology::Square, Topology::Hex, Topology::Triangle] {
+			for (x, y) in (0..3).flat_map(|y| (0..4).map(move |x| (x, y))) {
+				let (ox, oy) = topology.origin(x, y);
+				let corners = topology.outline(x, y);
+				let n = corners.len() as f32;
+				let cx = ox + corners.iter().map(|c| c.0).sum::<f32>() / n;
+				let cy = oy + corners.iter().map(|c| c.1).sum::<f32>() / n;
+				assert_eq!(topology.cell_at(4, 3, cx, cy), Some((x, y)));
+			}
+			assert_eq!(topology.cell_at(4, 3, -0.5, -0.5), None);
+		}
+	}
+}